@@ -1,12 +1,147 @@
+use etscript_core::{Clock, Context, Database, Diagnostic, Engine, EtError, Whitespace};
 use std::{
-    env,
-    ffi::{CStr, CString},
-    fs,
-    io::{self, Write},
+    env, fs,
+    io::{self, IsTerminal, Write},
     process,
+    time::{Duration, SystemTime},
 };
 
-fn repl() {
+const USAGE: &str = "Usage: etscript [--attributes <file>] [--database <file|:memory:>] \
+                     [--read-only] [--strict] [--swallow-newlines] [--now <unix time>] \
+                     [--seed <number>] [file]";
+
+struct Args {
+    path: Option<String>,
+    attributes: Option<String>,
+    database: Option<String>,
+    is_read_only: bool,
+    is_strict: bool,
+    swallows_newlines: bool,
+    now: Option<u64>,
+    seed: Option<u64>,
+}
+
+impl Args {
+    fn parse() -> Result<Self, String> {
+        let mut args = Self {
+            path: None,
+            attributes: None,
+            database: None,
+            is_read_only: false,
+            is_strict: false,
+            swallows_newlines: false,
+            now: None,
+            seed: None,
+        };
+        let mut iter = env::args().skip(1);
+
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--attributes" | "-a" => {
+                    let Some(value) = iter.next() else {
+                        return Err(format!("Missing value for `{arg}`."));
+                    };
+                    args.attributes = Some(value);
+                }
+                "--database" | "-d" => {
+                    let Some(value) = iter.next() else {
+                        return Err(format!("Missing value for `{arg}`."));
+                    };
+                    args.database = Some(value);
+                }
+                "--read-only" => args.is_read_only = true,
+                "--strict" => args.is_strict = true,
+                "--swallow-newlines" => args.swallows_newlines = true,
+                "--now" | "--seed" => {
+                    let Some(value) = iter.next() else {
+                        return Err(format!("Missing value for `{arg}`."));
+                    };
+                    let Ok(value) = value.parse() else {
+                        return Err(format!("`{arg}` expects a whole number, not `{value}`."));
+                    };
+                    match arg.as_str() {
+                        "--now" => args.now = Some(value),
+                        _ => args.seed = Some(value),
+                    }
+                }
+                _ if arg.starts_with('-') => return Err(format!("Unknown option: `{arg}`.")),
+                _ if args.path.is_none() => args.path = Some(arg),
+                _ => return Err("Only one source file can be run at a time.".to_owned()),
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+/// Reads attributes from a file with one `name=value` pair per line, e.g.:
+///
+/// ```text
+/// # subscriber #1
+/// First Name=Ann
+/// EmailAddress=ann@example.com
+/// ```
+fn attributes(path: &str) -> Result<Context, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| format!("Unable to read `{path}`: {err}"))?;
+    let mut context = Context::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once('=') else {
+            return Err(format!(
+                "{path}:{} — expected an attribute in the form of `name=value`.",
+                i + 1
+            ));
+        };
+        context.set_attribute(name.trim(), value.trim());
+    }
+
+    Ok(context)
+}
+
+/// Chooses the database for data extension functions, which is `./etscript.db` unless given.
+fn database(args: &Args) -> Result<Database, String> {
+    let database = match args.database.as_deref() {
+        Some(":memory:") => Database::in_memory().map_err(|err| err.to_string())?,
+        Some(path) => Database::file(path),
+        None => Database::default(),
+    };
+
+    Ok(database.read_only(args.is_read_only))
+}
+
+/// Renders each diagnostic with the line of source it points to, e.g.:
+///
+/// ```text
+/// error: Expected expression.
+///  --> line 1, column 14
+///   |
+/// 1 | %%[ set @a = ]%%
+///   |              ^^^
+/// ```
+fn report(diagnostics: &[Diagnostic], source: &str, is_colored: bool) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic.render(source, is_colored))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Reports an error on stdout, which is where output and errors have always gone.
+fn report_error(err: &EtError, source: &str) -> String {
+    // errors that don't come from the source, e.g., a database that can't be opened
+    if err.diagnostics().is_empty() {
+        return err.to_string();
+    }
+
+    report(err.diagnostics(), source, io::stdout().is_terminal())
+}
+
+fn repl(engine: &Engine, context: &Context) {
     println!("ETscript 0.1.1 (press Control-C to quit)");
 
     loop {
@@ -25,36 +160,80 @@ fn repl() {
                 line.pop();
             }
         }
-        let c_string = CString::new(line).unwrap();
-        unsafe {
-            let result = etscript_core::interpret(c_string.as_ptr());
-            let str = CStr::from_ptr((*result).value).to_str().unwrap();
-
-            println!("{str}");
-            etscript_core::free_result(result);
-        };
+        match engine.render_with(&line, context) {
+            Ok(rendered) => println!("{}", rendered.output),
+            Err(err) => {
+                // like a file, whatever was rendered before the error comes first
+                let report = report_error(&err, &line);
+                match err.partial_output() {
+                    Some(output) if !output.is_empty() => println!("{output}\n{report}"),
+                    _ => println!("{report}"),
+                }
+            }
+        }
     }
 }
 
-fn file(path: &str) {
+fn file(engine: &Engine, path: &str, context: &Context) {
     let source = fs::read_to_string(path).expect("Contents of a file should have been read.");
-    let c_string = CString::new(source).unwrap();
-    unsafe {
-        let result = etscript_core::interpret(c_string.as_ptr());
-        let str = CStr::from_ptr((*result).value).to_str().unwrap();
 
-        println!("{str}");
-        etscript_core::free_result(result);
+    let template = match engine.compile(&source) {
+        Ok(template) => template,
+        Err(err) => {
+            println!("{}", report_error(&err, &source));
+            return;
+        }
+    };
+    // warnings go to stderr so that they don't end up in the output
+    if !template.warnings().is_empty() {
+        let report = report(template.warnings(), &source, io::stderr().is_terminal());
+        eprintln!("{report}\n");
+    }
+    let mut stdout = io::stdout().lock();
+
+    // output is streamed, so a runtime error follows whatever was written before it
+    match template.render_to(context, &mut stdout) {
+        Ok(_) => writeln!(stdout).ok(),
+        Err(err) => writeln!(stdout, "\n{}", report_error(&err, &source)).ok(),
     };
 }
 
 fn main() {
-    match env::args().count() {
-        1 => repl(),
-        2 => file(&env::args().last().unwrap()),
-        _ => {
-            eprintln!("Usage: etscript [file]");
-            process::exit(64);
+    let args = Args::parse().unwrap_or_else(|err| {
+        eprintln!("{err}\n{USAGE}");
+        process::exit(64);
+    });
+    let context = match &args.attributes {
+        Some(path) => attributes(path).unwrap_or_else(|err| {
+            eprintln!("{err}");
+            process::exit(66);
+        }),
+        None => Context::new(),
+    };
+    let mut engine = Engine::new();
+
+    // a fixed time and seed make output repeatable, e.g., for snapshot tests
+    if let Some(unix_time) = args.now {
+        engine.set_clock(Clock::Fixed(
+            SystemTime::UNIX_EPOCH + Duration::from_secs(unix_time),
+        ));
+    }
+    engine.set_seed(args.seed);
+    engine.set_strict(args.is_strict);
+    if args.swallows_newlines {
+        engine.set_whitespace(Whitespace::SwallowNewline);
+    }
+
+    match database(&args) {
+        Ok(database) => engine.set_database(database),
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(74);
         }
     }
+
+    match &args.path {
+        Some(path) => file(&engine, path, &context),
+        None => repl(&engine, &context),
+    }
 }
@@ -0,0 +1,73 @@
+// The `etscript` binary run on a file, as a shell or a build script would run it.
+
+use std::{env, fs, path::PathBuf, process, process::Command};
+
+const ROW_COUNT: &str = "%%=DataExtensionRowCount(\"_test_table\")=%%";
+const INSERT: &str = "%%[ InsertDe(\"_test_table\", \"email\", \"ann@example.com\") ]%%\
+%%=DataExtensionRowCount(\"_test_table\")=%%";
+
+// a path of its own for each test, so that tests running at the same time don't share a file
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("etscript-cli-{}-{name}", process::id()))
+}
+
+fn run(args: &[&str], script: &PathBuf) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_etscript"))
+        .args(args)
+        .arg(script)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+//
+
+#[test]
+fn database_option_chooses_the_file() {
+    let script = temp_path("insert.ampscript");
+    let database = temp_path("chosen.db");
+    let database_arg = database.to_str().unwrap();
+    fs::write(&script, INSERT).unwrap();
+
+    assert_eq!(run(&["--database", database_arg], &script), "1\n");
+    // the row is still there for the next run
+    fs::write(&script, ROW_COUNT).unwrap();
+    assert_eq!(run(&["--database", database_arg], &script), "1\n");
+
+    fs::remove_file(&script).unwrap();
+    fs::remove_file(&database).unwrap();
+}
+
+#[test]
+fn read_only_database_refuses_writes() {
+    let script = temp_path("read-only.ampscript");
+    let database = temp_path("read-only.db");
+    let database_arg = database.to_str().unwrap();
+    // the first run creates and bootstraps the file, which a read-only run won't
+    fs::write(&script, ROW_COUNT).unwrap();
+    assert_eq!(run(&["--database", database_arg], &script), "0\n");
+
+    fs::write(&script, INSERT).unwrap();
+    let output = run(&["--database", database_arg, "--read-only"], &script);
+    assert!(output.contains("readonly database"), "{output}");
+
+    fs::write(&script, ROW_COUNT).unwrap();
+    assert_eq!(run(&["--database", database_arg], &script), "0\n");
+
+    fs::remove_file(&script).unwrap();
+    fs::remove_file(&database).unwrap();
+}
+
+#[test]
+fn in_memory_database_is_discarded() {
+    let script = temp_path("memory.ampscript");
+    fs::write(&script, INSERT).unwrap();
+
+    for _ in 0..2 {
+        assert_eq!(run(&["--database", ":memory:"], &script), "1\n");
+    }
+
+    fs::remove_file(&script).unwrap();
+}
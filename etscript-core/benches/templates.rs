@@ -0,0 +1,121 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use etscript_core::{Context, Database, Engine, HostValue};
+
+use std::collections::BTreeMap;
+
+// each template sticks to functions implemented in Rust so that only the VM is measured
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "personalization",
+        r#"%%[ var @greeting set @greeting = Concat("Hello, ", [First Name]) ]%%
+<html>
+  <body>
+    <h1>%%=v(@greeting)=%%!</h1>
+    <p>Your tier is %%=v(@tier)=%%, and your email is %%EmailAddress%%.</p>
+    <p>%%=Iif(@tier == "Gold", "Thanks for being a member.", "Upgrade today.")=%%</p>
+  </body>
+</html>"#,
+    ),
+    (
+        "arithmetic_loop",
+        r#"%%[
+var @total, @evens
+set @total = 0
+set @evens = 0
+for @i = 1 to 2000 do
+  set @total = Add(@total, @i)
+  if Mod(@i, 2) == 0 then
+    set @evens = Add(@evens, 1)
+  endif
+next @i
+]%%%%=v(@total)=%% %%=v(@evens)=%%"#,
+    ),
+    (
+        "comparisons",
+        r#"%%[
+var @hits, @low
+set @hits = 0
+for @i = 1 to 5000 do
+  set @low = @i < 2500
+  if not @low and @i != 4000 or @i == 7 then
+    set @hits = Add(@hits, 1)
+  endif
+next @i
+]%%%%=v(@hits)=%%"#,
+    ),
+    (
+        "rowset_loop",
+        r#"%%[
+var @row, @count
+set @count = RowCount(@offers)
+for @i = 1 to @count do
+  set @row = Row(@offers, @i)
+  if Field(@row, "points") > 50 then ]%%
+<li>%%=Field(@row, "name")=%%: %%=Field(@row, "points")=%%</li>%%[
+  endif
+next @i
+]%%"#,
+    ),
+    (
+        "string_building",
+        r#"%%[
+var @list, @item
+set @list = ""
+for @i = 1 to 500 do
+  set @item = Lowercase(Concat("ITEM-", @i))
+  if IndexOf(@item, "-1") == 5 then
+    set @item = Uppercase(@item)
+  endif
+  set @list = Concat(@list, @item, ",")
+next @i
+]%%%%=v(@list)=%%"#,
+    ),
+];
+
+fn context() -> Context {
+    Context::new()
+        .attribute("First Name", "Ann")
+        .attribute("EmailAddress", "ann@example.com")
+        .variable("@tier", "Gold")
+}
+
+fn offers() -> Vec<BTreeMap<String, HostValue>> {
+    (1..=200)
+        .map(|n| {
+            BTreeMap::from([
+                ("Name".to_owned(), HostValue::from(format!("Offer {n}"))),
+                ("Points".to_owned(), HostValue::from(n as f64 / 2.0)),
+            ])
+        })
+        .collect()
+}
+
+fn templates(c: &mut Criterion) {
+    let mut engine = Engine::new();
+    engine.set_database(Database::in_memory().expect("Database should have opened."));
+    // converting the rowset is part of every render, so only the template that uses it pays
+    let context_with_offers = context().variable("@offers", offers());
+    let context = context();
+
+    for (name, source) in TEMPLATES {
+        let template = engine
+            .compile(source)
+            .unwrap_or_else(|err| panic!("`{name}` should have compiled: {err}"));
+        let context = if source.contains("@offers") {
+            &context_with_offers
+        } else {
+            &context
+        };
+
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                template
+                    .render_with(context)
+                    .expect("Render should have succeeded.")
+            })
+        });
+    }
+}
+
+criterion_group!(benches, templates);
+criterion_main!(benches);
@@ -90,4 +90,15 @@ https://learn.microsoft.com/dotnet/core/deploying/native-aot/#platformarchitectu
         println!("cargo:rustc-link-lib={dl_name}");
     }
     println!("cargo:rustc-link-search=native={publish_path}");
+
+    // the C header ships alongside the library
+    cbindgen::Builder::new()
+        .with_crate(pkg_path_str)
+        .with_config(
+            cbindgen::Config::from_file(format!("{pkg_path_str}/cbindgen.toml"))
+                .expect("`cbindgen.toml` should have been read."),
+        )
+        .generate()
+        .expect("C header should have been generated.")
+        .write_to_file(format!("{r_path}/etscript.h"));
 }
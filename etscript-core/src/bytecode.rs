@@ -1,51 +1,106 @@
+use crate::object::Symbol;
 use crate::value::Value;
 
-pub enum Opcode {
-    Constant,
-    ConstantShort,
-    DefineGlobal,
-    DefineGlobalShort,
-    GetGlobal,
-    GetGlobalShort,
-    SetGlobal,
-    SetGlobalShort,
-    NativeFn,
-    NativeFnShort,
-    Call,
-
-    GetLocal,
-    SetLocal,
-
-    Jump,
-    JumpIfFalse,
-    Loop,
-    Pass,
-
-    Add,
-    Negate,
-    Null,
-    LineFeed,
-
-    True,
-    False,
-    Not,
-
-    Equal,
-    NotEqual,
-    Less,
-    LessEqual,
-    Greater,
-    GreaterEqual,
-
-    Pop,
-    Write,
-    Return,
+// declares `Opcode` along with `Opcode::ALL`, so that every opcode can be decoded
+macro_rules! opcodes {
+    ($(#[$meta:meta])* $vq:vis enum $name:ident {
+        $($variant:ident),*$(,)?
+    }) => {
+        $(#[$meta])*
+        $vq enum $name {
+            $($variant),*
+        }
+
+        impl $name {
+            // indexed by discriminant
+            const ALL: &'static [Self] = &[$(Self::$variant),*];
+        }
+    };
+}
+
+opcodes! {
+    #[repr(u8)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Opcode {
+        Constant,
+        ConstantShort,
+        DefineGlobal,
+        DefineGlobalShort,
+        DeclareGlobal,
+        DeclareGlobalShort,
+        GetGlobal,
+        GetGlobalShort,
+        SetGlobal,
+        SetGlobalShort,
+        GetAttr,
+        GetAttrShort,
+        NativeFn,
+        NativeFnShort,
+        Call,
+
+        GetLocal,
+        SetLocal,
+
+        Jump,
+        JumpIfFalse,
+        Loop,
+        Pass,
+
+        Add,
+        Negate,
+        Null,
+        LineFeed,
+
+        True,
+        False,
+        Not,
+
+        Equal,
+        NotEqual,
+        Less,
+        LessEqual,
+        Greater,
+        GreaterEqual,
+
+        Pop,
+        Write,
+        Return,
+    }
+}
+
+impl Opcode {
+    /// Whether the instruction's operand takes two bytes instead of one.
+    pub fn is_short(self) -> bool {
+        matches!(
+            self,
+            Self::ConstantShort
+                | Self::DefineGlobalShort
+                | Self::DeclareGlobalShort
+                | Self::GetGlobalShort
+                | Self::SetGlobalShort
+                | Self::GetAttrShort
+                | Self::NativeFnShort
+        )
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    /// Fails with `byte` if it isn't an opcode.
+    fn try_from(byte: u8) -> Result<Self, u8> {
+        Self::ALL.get(byte as usize).copied().ok_or(byte)
+    }
 }
 
 pub struct Bytecode {
     bytes: Vec<u8>,
     constants: Vec<Value>,
+    /// The name of each global variable, indexed by the slot the compiler assigned it.
+    globals: Vec<Symbol>,
     line_nums: Vec<u16>,
+    /// Where in the source the token that each byte was compiled from begins.
+    source_offsets: Vec<u32>,
 }
 
 impl Default for Bytecode {
@@ -59,7 +114,9 @@ impl Bytecode {
         Self {
             bytes: Vec::new(),
             constants: Vec::new(),
+            globals: Vec::new(),
             line_nums: Vec::new(),
+            source_offsets: Vec::new(),
         }
     }
 
@@ -68,15 +125,20 @@ impl Bytecode {
         self.bytes.shrink_to_fit();
         self.constants.clear();
         self.constants.shrink_to_fit();
+        self.globals.clear();
+        self.globals.shrink_to_fit();
         self.line_nums.clear();
         self.line_nums.shrink_to_fit();
+        self.source_offsets.clear();
+        self.source_offsets.shrink_to_fit();
     }
 
     //
 
-    pub fn push_byte(&mut self, byte: u8, line_num: u16) {
+    pub fn push_byte(&mut self, byte: u8, line_num: u16, source_offset: usize) {
         self.bytes.push(byte);
         self.line_nums.push(line_num);
+        self.source_offsets.push(source_offset as u32);
     }
 
     pub fn assign(&mut self, offset: usize, byte: u8) {
@@ -109,7 +171,27 @@ impl Bytecode {
 
     //
 
+    pub fn push_global(&mut self, name: Symbol) -> usize {
+        self.globals.push(name);
+
+        self.globals.len() - 1
+    }
+
+    pub fn global_at(&self, slot: usize) -> Symbol {
+        self.globals[slot]
+    }
+
+    pub fn globals(&self) -> &[Symbol] {
+        &self.globals
+    }
+
+    //
+
     pub fn line_num_at(&self, offset: usize) -> u16 {
         self.line_nums[offset]
     }
+
+    pub fn source_offset_at(&self, offset: usize) -> usize {
+        self.source_offsets[offset] as usize
+    }
 }
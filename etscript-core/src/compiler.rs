@@ -1,10 +1,13 @@
-use crate::bytecode::Opcode;
+use crate::bytecode::{Bytecode, Opcode};
 use crate::debug;
+use crate::diagnostic;
 use crate::lexer::{Lexer, Mode, Token, TokenType};
+use crate::object::{self, Symbol};
 use crate::value::Value;
 use crate::vm::{Status, Vm};
+use crate::{Diagnostic, ErrorKind, Severity};
 
-use std::collections::HashMap;
+use std::{collections::HashMap, mem};
 use unicode_segmentation::UnicodeSegmentation;
 
 struct Local {
@@ -133,7 +136,12 @@ r.insert(TokenType::Eof as u8,             ParseRule::default());
 
 pub struct Compiler<'a> {
     vm: &'a mut Vm,
+    bc: Bytecode,
     locals: Vec<Local>,
+    /// The slot assigned to each global variable.
+    globals: HashMap<Symbol, usize>,
+    /// How each global was first spelled, since other spellings name the same variable.
+    spellings: HashMap<usize, &'a str>,
     scope_depth: isize,
     parser: Parser<'a>,
 }
@@ -143,13 +151,19 @@ impl<'a> Compiler<'a> {
     const BYTE_JUMP_MAX: u16 = u16::MAX;
     const ARG_COUNT_MAX: u8 = u8::MAX;
 
-    pub fn new(vm: &'a mut Vm, source: &'static str) -> Self {
+    pub fn new(vm: &'a mut Vm, source: &'a str) -> Self {
+        let mut lexer = Lexer::new(source);
+        lexer.whitespace = vm.whitespace;
+
         Self {
             vm,
+            bc: Bytecode::new(),
             locals: Vec::with_capacity(Self::CONSTANTS_MAX),
+            globals: HashMap::new(),
+            spellings: HashMap::new(),
             scope_depth: 0,
             parser: Parser {
-                lexer: Lexer::new(source),
+                lexer,
                 rules: HashMap::new(),
                 previous_token: Token::default(),
                 current_token: Token::default(),
@@ -177,6 +191,8 @@ impl<'a> Compiler<'a> {
 
     fn init(&mut self) {
         self.init_locals();
+        self.globals.clear();
+        self.spellings.clear();
         self.init_parser();
     }
 
@@ -187,17 +203,17 @@ impl<'a> Compiler<'a> {
             && cfg!(debug_assertions)
             && option_env!("PRINT_BYTECODE").is_some()
         {
-            debug::disassemble_bytecode(&self.vm.bc, "Instruction Set", &self.vm.strings.borrow());
+            debug::disassemble_bytecode(&self.bc, "Instruction Set", &self.vm.strings);
         }
     }
 
     //
 
-    fn str_from_src(&self, start: usize, length: usize) -> &'static str {
+    fn str_from_src(&self, start: usize, length: usize) -> &'a str {
         &self.parser.lexer.source[start..start + length]
     }
 
-    fn str_from_token(&self, token: Token) -> &'static str {
+    fn str_from_token(&self, token: Token) -> &'a str {
         self.str_from_src(token.offset, token.length)
     }
 
@@ -207,29 +223,71 @@ impl<'a> Compiler<'a> {
         }
         self.parser.in_panic_mode = true;
 
-        *self.vm.result.borrow_mut() += &format!("[line {}] Error", token.line_num);
+        if self.vm.error_kind.is_none() {
+            self.vm.error_kind = Some(match token.type_ {
+                TokenType::Error => ErrorKind::Lex,
+                _ => ErrorKind::Parse,
+            });
+        }
+        let (line, column) = self.position_of(token);
+        self.vm.error += &format!("[line {line}] Error");
 
         match token.type_ {
             TokenType::Error => {
-                *self.vm.result.borrow_mut() +=
-                    &self.parser.lexer.err_fmt_string.replace("{}", err_msg);
+                self.vm.error += &self.parser.lexer.err_fmt_string.replace("{}", err_msg);
             }
             TokenType::Eof => {
-                *self.vm.result.borrow_mut() += " at end.";
+                self.vm.error += " at end.";
             }
             _ => {
-                *self.vm.result.borrow_mut() += &format!(" at `{}`.", self.str_from_token(token));
+                self.vm.error += &format!(" at `{}`.", self.str_from_token(token));
             }
         }
 
         if token.type_ != TokenType::Error {
-            *self.vm.result.borrow_mut() += &format!(" {err_msg}");
+            self.vm.error += &format!(" {err_msg}");
         }
-        *self.vm.result.borrow_mut() += "\n";
+        self.vm.error += "\n";
+
+        let message = if token.type_ == TokenType::Error {
+            let message = self.parser.lexer.err_fmt_string.replace("{}", err_msg);
+            let message = message.trim_start_matches(" — ");
+            let mut chars = message.chars();
+
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        } else {
+            err_msg.to_owned()
+        };
+        self.vm.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            line,
+            column,
+            span: Some(token.offset..token.offset + token.length),
+            message,
+        });
 
         self.parser.had_error = true;
     }
 
+    fn warning_at(&mut self, token: Token, message: String) {
+        let (line, column) = self.position_of(token);
+
+        self.vm.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            line,
+            column,
+            span: Some(token.offset..token.offset + token.length),
+            message,
+        });
+    }
+
+    fn position_of(&self, token: Token) -> (u32, u32) {
+        diagnostic::position_at(self.parser.lexer.source, token.offset)
+    }
+
     fn error(&mut self, err_msg: &str) {
         self.error_at(self.parser.previous_token, err_msg);
     }
@@ -291,7 +349,12 @@ impl<'a> Compiler<'a> {
             return false;
         }
 
-        self.str_from_token(a) == self.str_from_token(b)
+        if self.vm.is_strict {
+            self.str_from_token(a) == self.str_from_token(b)
+        } else {
+            self.str_from_token(a)
+                .eq_ignore_ascii_case(self.str_from_token(b))
+        }
     }
 
     fn is_const_short(&self, offset: usize) -> bool {
@@ -301,9 +364,8 @@ impl<'a> Compiler<'a> {
     //
 
     fn emit_byte(&mut self, byte: u8) {
-        self.vm
-            .bc
-            .push_byte(byte, self.parser.previous_token.line_num);
+        let token = self.parser.previous_token;
+        self.bc.push_byte(byte, token.line_num, token.offset);
     }
 
     fn emit_bytes(&mut self, byte: u8, offset: usize) {
@@ -346,28 +408,28 @@ impl<'a> Compiler<'a> {
         self.emit_byte(0xff);
         self.emit_byte(0xff);
 
-        self.vm.bc.byte_count() - 2
+        self.bc.byte_count() - 2
     }
 
     fn patch_jump(&mut self, offset: usize) {
-        let jump = self.vm.bc.byte_count() - offset - 2;
+        let jump = self.bc.byte_count() - offset - 2;
         if jump > Self::BYTE_JUMP_MAX as usize {
             self.error("Jump size limit exceeded.");
         }
 
-        self.vm.bc.assign(offset, ((jump >> 8) & 0xff) as u8);
-        self.vm.bc.assign(offset + 1, (jump & 0xff) as u8);
+        self.bc.assign(offset, ((jump >> 8) & 0xff) as u8);
+        self.bc.assign(offset + 1, (jump & 0xff) as u8);
     }
 
     //
 
     fn push_const(&mut self, val: Value) -> usize {
-        if self.vm.bc.const_count() >= Self::CONSTANTS_MAX {
+        if self.bc.const_count() >= Self::CONSTANTS_MAX {
             self.error("Constant pool size limit reached.");
             return 0;
         }
 
-        self.vm.bc.push_const(val)
+        self.bc.push_const(val)
     }
 
     fn emit_const(&mut self, val: Value) {
@@ -391,6 +453,34 @@ impl<'a> Compiler<'a> {
         self.push_const(val)
     }
 
+    fn resolve_global(&mut self, name: Token) -> usize {
+        let spelling = self.str_from_token(name);
+        let ident = self.vm.fold_name(spelling);
+        let symbol = object::intern(ident, self.vm);
+
+        if let Some(&slot) = self.globals.get(&symbol) {
+            let first_spelling = self.spellings[&slot];
+            if spelling != first_spelling {
+                let message = format!(
+                    "`{spelling}` is the same variable as `{first_spelling}`, since variable names \
+                     ignore case."
+                );
+                self.warning_at(name, message);
+            }
+            return slot;
+        }
+        if self.globals.len() >= Self::CONSTANTS_MAX {
+            self.error("Global variable limit reached.");
+            return 0;
+        }
+
+        let slot = self.bc.push_global(symbol);
+        self.globals.insert(symbol, slot);
+        self.spellings.insert(slot, spelling);
+
+        slot
+    }
+
     fn resolve_local(&mut self, name: &Token) -> isize {
         let mut local_offset = -1;
         let mut had_error = false;
@@ -453,10 +543,11 @@ impl<'a> Compiler<'a> {
             return 0; // local scope; exit before globals are parsed
         }
 
-        self.push_ident_const(self.parser.previous_token)
+        self.resolve_global(self.parser.previous_token)
     }
 
-    fn define_var(&mut self, ident_const: usize) {
+    // a `var` declaration leaves a global the host defined as it is, unlike `set`
+    fn define_var(&mut self, slot: usize, is_declaration: bool) {
         if self.scope_depth > 0 {
             if let Some(local) = self.locals.last_mut() {
                 local.depth = self.scope_depth; // local is now initialized
@@ -464,13 +555,14 @@ impl<'a> Compiler<'a> {
             return; // local scope; exit before globals are parsed
         }
 
-        let opcode = if self.is_const_short(ident_const) {
-            Opcode::DefineGlobalShort
-        } else {
-            Opcode::DefineGlobal
+        let opcode = match (is_declaration, self.is_const_short(slot)) {
+            (true, true) => Opcode::DeclareGlobalShort,
+            (true, false) => Opcode::DeclareGlobal,
+            (false, true) => Opcode::DefineGlobalShort,
+            (false, false) => Opcode::DefineGlobal,
         };
 
-        self.emit_bytes(opcode as u8, ident_const);
+        self.emit_bytes(opcode as u8, slot);
     }
 
     fn emit_var(&mut self, name: Token, can_assign: bool) {
@@ -482,8 +574,18 @@ impl<'a> Compiler<'a> {
         if ident != -1 {
             get_op = Opcode::GetLocal;
             set_op = Opcode::SetLocal;
-        } else {
+        } else if !is_var {
+            // attributes are read-only; assignment is reported as an error below
             ident = self.push_ident_const(name) as isize;
+            if self.is_const_short(ident as usize) {
+                get_op = Opcode::GetAttrShort;
+                set_op = Opcode::GetAttrShort;
+            } else {
+                get_op = Opcode::GetAttr;
+                set_op = Opcode::GetAttr;
+            }
+        } else {
+            ident = self.resolve_global(name) as isize;
             if self.is_const_short(ident as usize) {
                 get_op = Opcode::GetGlobalShort;
                 set_op = Opcode::SetGlobalShort;
@@ -762,7 +864,7 @@ impl<'a> Compiler<'a> {
         let ident = self.parse_var("Expected variable name.");
 
         self.emit_byte(Opcode::Null as u8);
-        self.define_var(ident);
+        self.define_var(ident, true);
         if self.is_at_token(TokenType::Equal) {
             self.error("A `var` declaration is not for assignment. Use `set` instead.");
         }
@@ -780,7 +882,7 @@ impl<'a> Compiler<'a> {
             self.error("Expected variable value.");
         }
 
-        self.define_var(ident);
+        self.define_var(ident, false);
     }
 
     fn parse_if_body(&mut self) {
@@ -859,7 +961,7 @@ impl<'a> Compiler<'a> {
         let init_var = self.parser.previous_token;
         if self.is_at_token(TokenType::Equal) {
             self.expr();
-            self.define_var(ident);
+            self.define_var(ident, false);
             self.emit_var(init_var, true);
         } else {
             self.error("Expected starting index assignment.");
@@ -884,7 +986,7 @@ impl<'a> Compiler<'a> {
         }
 
         // ending index expression
-        let loop_ = self.vm.bc.byte_count();
+        let loop_ = self.bc.byte_count();
         self.expr();
         self.emit_byte(comp_op);
 
@@ -924,7 +1026,7 @@ impl<'a> Compiler<'a> {
         // emit loop
         self.emit_byte(Opcode::Loop as u8);
 
-        let offset = self.vm.bc.byte_count() - loop_ + 2;
+        let offset = self.bc.byte_count() - loop_ + 2;
         if offset > Self::BYTE_JUMP_MAX as usize {
             self.error("Loop size limit exceeded.");
         }
@@ -974,6 +1076,19 @@ impl<'a> Compiler<'a> {
                 return;
             }
             match self.parser.current_token.type_ {
+                // the end of a block, and text or blocks after it, start over cleanly
+                TokenType::BlockDelimClose => return,
+                TokenType::BlockDelimOpen => return,
+                TokenType::Pass => return,
+                TokenType::FnDelimClose => {
+                    self.advance();
+                    return;
+                }
+                TokenType::AttrDelim => {
+                    self.parser.lexer.mode = Mode::Pass; // see `inline_expr()`
+                    self.advance();
+                    return;
+                }
                 TokenType::Var => return,
                 TokenType::Set => return,
                 TokenType::If => return,
@@ -992,8 +1107,19 @@ impl<'a> Compiler<'a> {
         while !self.is_token_type(TokenType::BlockDelimClose) && !self.is_token_type(TokenType::Eof)
         {
             self.decl_stmt();
+
+            // an error can consume the end of the block, e.g., `set @a = ]%%`
+            if self.parser.had_error
+                && self.parser.previous_token.type_ == TokenType::BlockDelimClose
+            {
+                return;
+            }
+        }
+        if self.parser.lexer.mode == Mode::Script {
+            self.consume(TokenType::BlockDelimClose, "Expected `</script>` tag.");
+        } else {
+            self.consume(TokenType::BlockDelimClose, "Expected `]%%` delimiter.");
         }
-        self.consume(TokenType::BlockDelimClose, "Expected `]%%` delimiter.");
     }
 
     fn block_egress(&mut self) {
@@ -1044,7 +1170,7 @@ impl<'a> Compiler<'a> {
 
     //
 
-    pub fn compile(&mut self) -> Result<(), Status> {
+    pub fn compile(&mut self) -> Result<Bytecode, Status> {
         self.init();
 
         //self.begin_scope();
@@ -1059,6 +1185,6 @@ impl<'a> Compiler<'a> {
             return Err(Status::CompileError);
         }
 
-        Ok(())
+        Ok(mem::take(&mut self.bc))
     }
 }
@@ -1,44 +1,27 @@
 use crate::functions::NResult;
 use crate::object::{self, ObjType};
 use crate::value::{Value, ValueType};
-use crate::vm::Vm;
-use crate::{falsey_pattern, truthy_pattern};
+use crate::vm::{Status, Vm};
+use crate::{falsey_pattern, truthy_pattern, ErrorKind, EtError};
 
 use libc::c_longlong;
-use rusqlite::{Connection, Result, Statement, ToSql};
-use std::{collections::HashMap, path::PathBuf};
+use rusqlite::{Connection, OpenFlags, Result, Statement, ToSql};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
 
 extern "C" {
     fn n_system_time_from_unix_time(unix_time: c_longlong) -> *const NResult;
 }
 
-pub fn local() -> PathBuf {
-    PathBuf::from("./etscript.db")
-}
-
-pub fn init() -> Result<()> {
-    let db = Connection::open(local())?;
-
-    /*
-        db.execute(
-            r#"
-    CREATE TABLE IF NOT EXISTS _test_attributes (
-        memberid           INTEGER  PRIMARY KEY,
-        replyemailaddress  TEXT,
-        replyname          TEXT,
-        member_busname     TEXT,
-        member_addr        TEXT,
-        member_city        TEXT,
-        member_state       TEXT,
-        member_postalcode  TEXT,
-        member_country     TEXT
-    )"#,
-            (),
-        )?;
-        */
-
-    db.execute(
-        r#"
+const TEST_TABLE: &str = r#"
 CREATE TABLE IF NOT EXISTS _test_table (
     email     TEXT      NOT NULL  PRIMARY KEY,
     text      TEXT,
@@ -48,11 +31,170 @@ CREATE TABLE IF NOT EXISTS _test_table (
     datetime  DATETIME,
     phone     TEXT,
     locale    TEXT
-)"#,
-        (),
-    )?;
+)"#;
+
+/// The SQLite database that data extension functions read from and write to.
+///
+/// The default is `./etscript.db`, bootstrapped with a `_test_table` for trying out the data
+/// extension functions.
+///
+/// ```ignore
+/// let database = Database::in_memory()?.bootstrap(include_str!("schema.sql"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Database {
+    location: Location,
+    is_read_only: bool,
+    bootstrap: String,
+    // shared by clones so that the bootstrap runs once, even when renders on several threads
+    // make their first data extension call at the same time
+    is_bootstrapped: Arc<Mutex<bool>>,
+}
 
-    Ok(())
+#[derive(Clone, Debug)]
+enum Location {
+    File(PathBuf),
+    Memory(Arc<MemoryDb>),
+}
+
+/// A shared-cache, in-memory database lives only as long as one of its connections does.
+struct MemoryDb {
+    uri: String,
+    anchor: Mutex<Connection>,
+}
+
+impl fmt::Debug for MemoryDb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.uri)
+    }
+}
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::file("./etscript.db")
+    }
+}
+
+impl Database {
+    fn new(location: Location) -> Self {
+        Self {
+            location,
+            is_read_only: false,
+            bootstrap: TEST_TABLE.to_owned(),
+            is_bootstrapped: Arc::default(),
+        }
+    }
+
+    /// A database file, which is created if it doesn't exist (unless it's read-only).
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        Self::new(Location::File(path.into()))
+    }
+
+    /// A database that is discarded once the engine and every template compiled by it are
+    /// dropped. Renders from the same engine share it.
+    pub fn in_memory() -> Result<Self, EtError> {
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let uri = format!(
+            "file:etscript-{}-{}?mode=memory&cache=shared",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let anchor = Connection::open_with_flags(&uri, OpenFlags::default()).map_err(|err| {
+            EtError::new(Status::DatabaseError, ErrorKind::Database, err.to_string())
+        })?;
+
+        Ok(Self::new(Location::Memory(Arc::new(MemoryDb {
+            uri,
+            anchor: Mutex::new(anchor),
+        }))))
+    }
+
+    /// Renders can only read from a read-only database, and a data extension function that
+    /// writes to one fails with `ErrorKind::Database`. The bootstrap still runs for an
+    /// in-memory database but is skipped for a file.
+    pub fn read_only(mut self, is_read_only: bool) -> Self {
+        self.is_read_only = is_read_only;
+        self
+    }
+
+    /// SQL that prepares the database in place of creating `_test_table`, e.g., a schema of
+    /// `CREATE TABLE IF NOT EXISTS` statements. An empty string skips the bootstrap.
+    pub fn bootstrap(mut self, sql: impl Into<String>) -> Self {
+        self.bootstrap = sql.into();
+        self.is_bootstrapped = Arc::default();
+        self
+    }
+
+    pub(crate) fn open(&self) -> Result<Connection> {
+        let mut flags = OpenFlags::default();
+        if self.is_read_only {
+            flags.remove(OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE);
+            flags.insert(OpenFlags::SQLITE_OPEN_READ_ONLY);
+        }
+
+        let db = match &self.location {
+            Location::File(path) => Connection::open_with_flags(path, flags)?,
+            Location::Memory(memory_db) => Connection::open_with_flags(&memory_db.uri, flags)?,
+        };
+        // a connection to a shared cache writes through the cache's read-write connection,
+        // whatever its own flags are
+        if self.is_read_only {
+            db.pragma_update(None, "query_only", true)?;
+        }
+
+        Ok(db)
+    }
+
+    /// Runs the bootstrap once for this database and any of its clones.
+    pub(crate) fn prepare(&self) -> Result<()> {
+        let mut is_bootstrapped = self
+            .is_bootstrapped
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if *is_bootstrapped {
+            return Ok(());
+        }
+
+        match &self.location {
+            Location::File(_) if self.is_read_only => (),
+            Location::File(path) => Connection::open(path)?.execute_batch(&self.bootstrap)?,
+            Location::Memory(memory_db) => {
+                let anchor = memory_db
+                    .anchor
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner());
+                anchor.execute_batch(&self.bootstrap)?;
+            }
+        }
+        *is_bootstrapped = true;
+
+        Ok(())
+    }
+}
+
+impl From<PathBuf> for Database {
+    fn from(path: PathBuf) -> Self {
+        Self::file(path)
+    }
+}
+
+impl From<&Path> for Database {
+    fn from(path: &Path) -> Self {
+        Self::file(path)
+    }
+}
+
+impl From<&str> for Database {
+    fn from(path: &str) -> Self {
+        Self::file(path)
+    }
+}
+
+impl From<String> for Database {
+    fn from(path: String) -> Self {
+        Self::file(path)
+    }
 }
 
 //
@@ -66,10 +208,13 @@ pub struct Table<'a> {
 }
 
 impl<'a> Table<'a> {
-    pub fn new(name: &'a str) -> Result<Self, Value> {
+    pub fn new(name: &'a str, vm: &mut Vm) -> Result<Self, Value> {
         Self::check_name(name, "Table")?;
+        vm.count_de_call()?;
 
-        let db = Connection::open(local())?;
+        // source that doesn't call a data extension function never opens the database
+        vm.database.prepare()?;
+        let db = vm.database.open()?;
         Self::table_exists(name, &db)?;
         let cols = Self::get_cols(name, &db)?;
 
@@ -82,10 +227,15 @@ impl<'a> Table<'a> {
         ))?;
 
         let err: Result<String, rusqlite::Error> =
-            Err(Value::error("Database error.".to_owned()).into());
+            Err(Value::error_of(ErrorKind::Database, "Database error.".to_owned()).into());
         match stmt.query_row([table], |row| row.get(0).or(err)) {
             Ok(_) => String::with_capacity(0),
-            Err(_) => return Err(Value::error(format!("No such table: {table}"))),
+            Err(_) => {
+                return Err(Value::error_of(
+                    ErrorKind::Database,
+                    format!("No such table: {table}"),
+                ))
+            }
         };
 
         Ok(())
@@ -98,9 +248,10 @@ impl<'a> Table<'a> {
         ))?;
 
         let Some(cols): Option<String> = stmt.query_row([], |row| row.get(0))? else {
-            return Err(Value::error(format!(
-                "Table `{table}` was not found or is not configured."
-            )));
+            return Err(Value::error_of(
+                ErrorKind::Database,
+                format!("Table `{table}` was not found or is not configured."),
+            ));
         };
 
         Ok(cols)
@@ -108,18 +259,23 @@ impl<'a> Table<'a> {
 
     fn check_name(name: &str, of: &str) -> Result<(), Value> {
         if name.is_empty() {
-            return Err(Value::error(format!("{of} names cannot be empty.")));
+            return Err(Value::error_of(
+                ErrorKind::Database,
+                format!("{of} names cannot be empty."),
+            ));
         }
         if name.starts_with(|c: char| c.is_ascii_digit()) {
-            return Err(Value::error(format!(
-                "{of} names must begin with a letter or underscore."
-            )));
+            return Err(Value::error_of(
+                ErrorKind::Database,
+                format!("{of} names must begin with a letter or underscore."),
+            ));
         }
         for c in name.chars() {
             if !(c.is_ascii_alphabetic() || c.is_ascii_digit() || c == '_') {
-                return Err(Value::error(format!(
-                    "{of} names must use letters, numbers, or underscores."
-                )));
+                return Err(Value::error_of(
+                    ErrorKind::Database,
+                    format!("{of} names must use letters, numbers, or underscores."),
+                ));
             }
         }
 
@@ -134,7 +290,8 @@ impl<'a> Table<'a> {
             .map(|&col| col.split(':').collect::<Vec<_>>()[0])
             .collect::<Vec<_>>();
         if col_names.is_empty() {
-            return Err(Value::error(
+            return Err(Value::error_of(
+                ErrorKind::Database,
                 "Column name information is missing.".to_owned(),
             ));
         }
@@ -144,7 +301,8 @@ impl<'a> Table<'a> {
             .map(|&col| col.split(':').collect::<Vec<_>>()[1])
             .collect::<Vec<_>>();
         if col_types.is_empty() {
-            return Err(Value::error(
+            return Err(Value::error_of(
+                ErrorKind::Database,
                 "Column type information is missing.".to_owned(),
             ));
         }
@@ -162,7 +320,10 @@ impl<'a> Table<'a> {
             .iter()
             .position(|&col| col.eq_ignore_ascii_case(name))
         else {
-            return Err(Value::error(format!("No such column: {name}")));
+            return Err(Value::error_of(
+                ErrorKind::Database,
+                format!("No such column: {name}"),
+            ));
         };
 
         Ok(index)
@@ -238,9 +399,10 @@ pub fn append_sql_args(
     while offset < count {
         let val = &vm.stack[offset];
         if !val.is_string() {
-            return Err(Value::error(format!(
-                "Name from name-value pair (#{ord}) must be a string."
-            )));
+            return Err(Value::error_of(
+                ErrorKind::Type,
+                format!("Name from name-value pair (#{ord}) must be a string."),
+            ));
         }
         let name = val.to_string(vm);
 
@@ -279,9 +441,10 @@ pub fn prep_stmt<'a>(
     const NULL: &str = "null";
 
     let err = |name, type_| {
-        Err(Value::error(format!(
-            "Unexpected type for column `{name}` — expected `{type_}`."
-        )))
+        Err(Value::error_of(
+            ErrorKind::Type,
+            format!("Unexpected type for column `{name}` — expected `{type_}`."),
+        ))
     };
 
     let col_data = &table.col_data()?;
@@ -294,10 +457,10 @@ pub fn prep_stmt<'a>(
 
         if i % 2 == 0 {
             if !val.is_string() {
-                return Err(Value::error(format!(
-                    "Column #{}'s name must be a string.",
-                    i / 2
-                )));
+                return Err(Value::error_of(
+                    ErrorKind::Type,
+                    format!("Column #{}'s name must be a string.", i / 2),
+                ));
             }
 
             let name = val.to_string(vm);
@@ -339,7 +502,7 @@ pub fn prep_stmt<'a>(
                 }
                 ValueType::Obj(obj) => match &obj.type_ {
                     ObjType::String(string_obj) => {
-                        let string = string_obj.get(&vm.strings.borrow()).to_ascii_lowercase();
+                        let string = string_obj.get(&vm.strings).to_ascii_lowercase();
                         match col_type {
                             t if t == SqlType::Boolean.to_lower() => match string.as_str() {
                                 truthy_pattern!() => param_data.push(Box::new(true)),
@@ -371,6 +534,8 @@ pub fn prep_stmt<'a>(
     }
 
     // table and column names are valid; `ToSql` parameter conversion complete
+    vm.observe_query(sql);
+
     Ok(table.db.prepare(sql)?)
 }
 
@@ -384,7 +549,7 @@ pub fn exec_stmt(
         .column_names()
         .into_iter()
         .map(|name| {
-            let string = name.to_ascii_lowercase();
+            let string = vm.fold_name(name);
             object::intern(string, vm)
         })
         .collect::<Vec<_>>();
@@ -455,7 +620,7 @@ impl From<rusqlite::Error> for Value {
         // todo: Some SQLite errors include too much info (e.g., raw queries).
         // Look into matching against:
         // https://docs.rs/rusqlite/latest/rusqlite/enum.Error.html
-        Value::error(err.to_string())
+        Value::error_of(ErrorKind::Database, err.to_string())
     }
 }
 
@@ -0,0 +1,107 @@
+use crate::debug::{STYLE_DIM, STYLE_RED_BOLD, STYLE_RESET, STYLE_YELLOW};
+
+use std::ops::Range;
+
+/// A problem found while compiling or running source code. Lines and columns begin with 1;
+/// a column of 0 means the position within the line is unknown. A runtime error points at the
+/// token that the failed instruction was compiled from, e.g., the `)` that ends a call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub line: u32,
+    pub column: u32,
+    /// The bytes of the source the diagnostic is about. Every compile diagnostic has one, which
+    /// is empty at the end of the source; runtime errors don't.
+    pub span: Option<Range<usize>>,
+    pub message: String,
+}
+
+/// The values are stable, and C callers receive them as `EtSeverity`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Compilation or execution stopped.
+    Error = 1,
+    /// Probably a mistake, but the source still compiles, e.g., a variable spelled two ways.
+    Warning = 2,
+}
+
+/// Returns the line and column of the byte at `offset` in `source`, both beginning with 1.
+/// Columns count characters.
+pub(crate) fn position_at(source: &str, offset: usize) -> (u32, u32) {
+    let offset = offset.min(source.len());
+    let line = source[..offset].matches('\n').count() as u32 + 1;
+    let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+
+    (line, source[line_start..offset].chars().count() as u32 + 1)
+}
+
+impl Diagnostic {
+    /// Formats the diagnostic along with the line of `source` it points to, underlining its
+    /// span with carets:
+    ///
+    /// ```text
+    /// error: Expected expression.
+    ///  --> line 1, column 14
+    ///   |
+    /// 1 | %%[ set @a = ]%%
+    ///   |              ^^^
+    /// ```
+    ///
+    /// `is_colored` adds ANSI colors, so it should only be set when writing to a terminal,
+    /// e.g., when `std::io::IsTerminal` says so.
+    pub fn render(&self, source: &str, is_colored: bool) -> String {
+        let style = |style: &'static str| if is_colored { style } else { "" };
+        let (label, color) = match self.severity {
+            Severity::Error => ("error", style(STYLE_RED_BOLD)),
+            Severity::Warning => ("warning", style(STYLE_YELLOW)),
+        };
+        let (dim, reset) = (style(STYLE_DIM), style(STYLE_RESET));
+
+        let mut lines = vec![format!("{color}{label}{reset}: {}", self.message)];
+        let text = match (self.line as usize).checked_sub(1) {
+            Some(i) => source.split('\n').nth(i),
+            None => None,
+        };
+        let Some(text) = text.map(|text| text.trim_end_matches('\r')) else {
+            return lines.remove(0);
+        };
+        let line_num = self.line.to_string();
+        let padding = " ".repeat(line_num.len());
+
+        if self.column == 0 {
+            lines.push(format!("{padding}{dim}-->{reset} line {}", self.line));
+        } else {
+            lines.push(format!(
+                "{padding}{dim}-->{reset} line {}, column {}",
+                self.line, self.column
+            ));
+        }
+        lines.push(format!("{padding} {dim}|{reset}"));
+        lines.push(format!("{dim}{line_num} |{reset} {text}"));
+
+        if self.column > 0 {
+            // tabs are kept so that the carets line up with the text above them
+            let indent = text
+                .chars()
+                .take(self.column as usize - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect::<String>();
+            // only the first line of a span is shown
+            let width = self
+                .span
+                .clone()
+                .and_then(|span| source.get(span))
+                .map_or(0, |spanned| {
+                    spanned.lines().next().unwrap_or("").chars().count()
+                });
+            let carets = "^".repeat(width.max(1));
+
+            lines.push(format!(
+                "{padding} {dim}|{reset} {indent}{color}{carets}{reset}"
+            ));
+        }
+
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,712 @@
+//! The C ABI. Every function here is a thin wrapper around the safe API in the crate root.
+//!
+//! A C header, `etscript.h`, is generated next to the library when the crate is built.
+
+use crate::vm::Status;
+use crate::{
+    CancelToken, Clock, Context, Database, Diagnostic, Engine, ErrorKind, EtError, HostValue,
+    Limits, Rendered, Severity,
+};
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    ptr,
+    time::{Duration, SystemTime},
+};
+
+/// An engine along with the attributes used for each of its renders.
+pub struct EtEngine {
+    engine: Engine,
+    context: Context,
+}
+
+/// Cancels renders from another thread.
+pub struct EtCancelToken {
+    token: CancelToken,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq)]
+pub enum EtValueKind {
+    Null = 0,
+    Number = 1,
+    Boolean = 2,
+    String = 3,
+}
+
+impl TryFrom<u32> for EtValueKind {
+    type Error = u32;
+
+    fn try_from(kind: u32) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(Self::Null),
+            1 => Ok(Self::Number),
+            2 => Ok(Self::Boolean),
+            3 => Ok(Self::String),
+            _ => Err(kind),
+        }
+    }
+}
+
+/// A value passed to or returned from a callback. Only the field matching `kind` is read.
+/// Rows and rowsets are passed as null.
+#[repr(C)]
+pub struct EtValue {
+    /// An `EtValueKind`. A value of any other kind is rejected.
+    pub kind: u32,
+    pub number: f64,
+    pub boolean: bool,
+    pub string: *const c_char,
+}
+
+/// A custom function. On success, the callback sets `out` and returns `true`. On failure, it
+/// returns `false` and may set `out->string` to an error message.
+///
+/// Strings in `args` are only valid for the duration of the call. A string set in `out` is
+/// copied as soon as the callback returns, so it must outlive the callback itself, e.g., by
+/// being owned by `user_data`.
+pub type EtCallback = unsafe extern "C" fn(
+    args: *const EtValue,
+    arg_count: usize,
+    out: *mut EtValue,
+    user_data: *mut c_void,
+) -> bool;
+
+#[repr(C)]
+pub struct EtDiagnostic {
+    pub severity: Severity,
+    pub line: u32,
+    /// 0 if unknown.
+    pub column: u32,
+    /// The byte offset of the span in the source, or -1 if unknown.
+    pub offset: isize,
+    pub length: usize,
+    pub message: *mut c_char,
+}
+
+/// Set an `EtLimits` field to this to leave that resource unlimited.
+pub const ETSCRIPT_UNLIMITED: u64 = u64::MAX;
+
+/// Resource limits for each render. A field set to `ETSCRIPT_UNLIMITED` is unlimited; any other
+/// value, including 0, is the limit, e.g., a `data_extension_calls` of 0 forbids data extension
+/// access.
+#[repr(C)]
+pub struct EtLimits {
+    pub instructions: u64,
+    pub output_bytes: u64,
+    pub wall_time_ms: u64,
+    /// Values above `UINT32_MAX` are unlimited.
+    pub data_extension_calls: u64,
+}
+
+#[repr(C)]
+pub struct EtRenderResult {
+    /// The output of a successful render, or whatever was rendered before an error.
+    pub output: *mut c_char,
+    pub status: Status,
+    /// An `EtErrorKind`, or 0 on success.
+    pub error_kind: i32,
+    /// The error's message, or `NULL` on success.
+    pub error: *mut c_char,
+    /// The function that returned the error, or `NULL`.
+    pub function: *mut c_char,
+    /// Where the error occurred, or 0 if unknown.
+    pub line: u32,
+    pub column: u32,
+    /// The compiler's errors and warnings, or the runtime error. A successful render still has
+    /// its warnings.
+    pub diagnostics: *mut EtDiagnostic,
+    pub diagnostic_count: usize,
+}
+
+fn c_string_or_null(string: Option<String>) -> *mut c_char {
+    string.map_or(ptr::null_mut(), c_string)
+}
+
+fn c_string(string: String) -> *mut c_char {
+    let err_string = CString::new("Error: The result contains an interior NUL character.").unwrap();
+
+    CString::new(string).unwrap_or(err_string).into_raw()
+}
+
+unsafe fn str_from<'a>(ptr: *const c_char) -> Option<&'a str> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    CStr::from_ptr(ptr).to_str().ok()
+}
+
+unsafe fn render_result(result: Result<Rendered, EtError>) -> *mut EtRenderResult {
+    let err = match result {
+        Ok(rendered) => {
+            // a successful render can still have compiler warnings
+            let (diagnostics, diagnostic_count) = c_diagnostics(&rendered.warnings);
+
+            return Box::into_raw(Box::new(EtRenderResult {
+                output: c_string(rendered.output),
+                status: Status::Ok,
+                error_kind: 0,
+                error: ptr::null_mut(),
+                function: ptr::null_mut(),
+                line: 0,
+                column: 0,
+                diagnostics,
+                diagnostic_count,
+            }));
+        }
+        Err(err) => err,
+    };
+    let (diagnostics, diagnostic_count) = c_diagnostics(err.diagnostics());
+
+    Box::into_raw(Box::new(EtRenderResult {
+        output: c_string(err.partial_output().unwrap_or_default().to_owned()),
+        status: err.status(),
+        error_kind: err.kind() as i32,
+        error: c_string(err.message().to_owned()),
+        function: c_string_or_null(err.function().map(str::to_owned)),
+        line: err.line(),
+        column: err.column(),
+        diagnostics,
+        diagnostic_count,
+    }))
+}
+
+fn c_diagnostics(diagnostics: &[Diagnostic]) -> (*mut EtDiagnostic, usize) {
+    let diagnostics = diagnostics
+        .iter()
+        .map(|diagnostic: &Diagnostic| EtDiagnostic {
+            severity: diagnostic.severity,
+            line: diagnostic.line,
+            column: diagnostic.column,
+            offset: diagnostic
+                .span
+                .as_ref()
+                .map_or(-1, |span| span.start as isize),
+            length: diagnostic.span.as_ref().map_or(0, |span| span.len()),
+            message: c_string(diagnostic.message.clone()),
+        })
+        .collect::<Box<[_]>>();
+    let diagnostic_count = diagnostics.len();
+
+    (
+        Box::into_raw(diagnostics) as *mut EtDiagnostic,
+        diagnostic_count,
+    )
+}
+
+//
+
+/// Creates an engine with the built-in functions and no attributes.
+#[no_mangle]
+pub extern "C" fn etscript_engine_new() -> *mut EtEngine {
+    Box::into_raw(Box::new(EtEngine {
+        engine: Engine::new(),
+        context: Context::new(),
+    }))
+}
+
+/// # Safety
+///
+/// `engine` must have been returned by `etscript_engine_new` and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_free(engine: *mut EtEngine) {
+    if !engine.is_null() {
+        drop(Box::from_raw(engine));
+    }
+}
+
+/// Sets an attribute for every subsequent render. Returns `false` if either string is not
+/// valid UTF-8.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `name` and `value` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_attribute(
+    engine: *mut EtEngine,
+    name: *const c_char,
+    value: *const c_char,
+) -> bool {
+    let (Some(name), Some(value)) = (str_from(name), str_from(value)) else {
+        return false;
+    };
+    (*engine).context.set_attribute(name, value);
+
+    true
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_clear_attributes(engine: *mut EtEngine) {
+    (*engine).context.clear_attributes();
+}
+
+/// Defines a variable such as `@segment` for every subsequent render. Returns `false` if
+/// `name` or a string value is not valid UTF-8, or if the value's kind is unknown.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, `name` must be a NUL-terminated string, and `value` must
+/// point to an `EtValue`.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_variable(
+    engine: *mut EtEngine,
+    name: *const c_char,
+    value: *const EtValue,
+) -> bool {
+    let Some(name) = str_from(name) else {
+        return false;
+    };
+    let value = &*value;
+    let Ok(kind) = EtValueKind::try_from(value.kind) else {
+        return false;
+    };
+    let value = match kind {
+        EtValueKind::Null => HostValue::Null,
+        EtValueKind::Number => HostValue::Number(value.number),
+        EtValueKind::Boolean => HostValue::Boolean(value.boolean),
+        EtValueKind::String => match str_from(value.string) {
+            Some(string) => HostValue::String(string.to_owned()),
+            None => return false,
+        },
+    };
+    (*engine).context.set_variable(name, value);
+
+    true
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_clear_variables(engine: *mut EtEngine) {
+    (*engine).context.clear_variables();
+}
+
+/// Sets the path of the SQLite database file used by data extension functions.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_database(
+    engine: *mut EtEngine,
+    path: *const c_char,
+) -> bool {
+    let Some(path) = str_from(path) else {
+        return false;
+    };
+    (*engine).engine.set_database(path);
+
+    true
+}
+
+/// Uses a database that is discarded along with the engine.
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_database_in_memory(engine: *mut EtEngine) -> bool {
+    let Ok(database) = Database::in_memory() else {
+        return false;
+    };
+    (*engine).engine.set_database(database);
+
+    true
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_database_read_only(
+    engine: *mut EtEngine,
+    is_read_only: bool,
+) {
+    let engine = &mut (*engine).engine;
+    engine.set_database(engine.database().clone().read_only(is_read_only));
+}
+
+/// Replaces the SQL that prepares the database, which otherwise creates `_test_table`. An empty
+/// string skips it. Call after choosing the database.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `sql` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_database_bootstrap(
+    engine: *mut EtEngine,
+    sql: *const c_char,
+) -> bool {
+    let Some(sql) = str_from(sql) else {
+        return false;
+    };
+    let engine = &mut (*engine).engine;
+    engine.set_database(engine.database().clone().bootstrap(sql));
+
+    true
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine, and `limits` must point to an `EtLimits`.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_limits(
+    engine: *mut EtEngine,
+    limits: *const EtLimits,
+) {
+    let limits = &*limits;
+    let limit = |max: u64| (max != ETSCRIPT_UNLIMITED).then_some(max);
+
+    (*engine).engine.set_limits(Limits {
+        instructions: limit(limits.instructions),
+        output_bytes: limit(limits.output_bytes),
+        wall_time: limit(limits.wall_time_ms).map(Duration::from_millis),
+        data_extension_calls: u32::try_from(limits.data_extension_calls).ok(),
+    });
+}
+
+/// Makes `Now()` return a fixed time, given in milliseconds since the Unix epoch.
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_fixed_time(engine: *mut EtEngine, unix_time_ms: i64) {
+    let since = Duration::from_millis(unix_time_ms.unsigned_abs());
+    let time = if unix_time_ms >= 0 {
+        SystemTime::UNIX_EPOCH + since
+    } else {
+        SystemTime::UNIX_EPOCH - since
+    };
+
+    (*engine).engine.set_clock(Clock::Fixed(time));
+}
+
+/// Makes `Now()` return the system's current time again.
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_system_time(engine: *mut EtEngine) {
+    (*engine).engine.set_clock(Clock::System);
+}
+
+/// Seeds `Random()` and `Guid()` so that each render produces the same values.
+///
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_seed(engine: *mut EtEngine, seed: u64) {
+    (*engine).engine.set_seed(Some(seed));
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_clear_seed(engine: *mut EtEngine) {
+    (*engine).engine.set_seed(None);
+}
+
+/// Makes every subsequent render stop once `token` is cancelled. Passing `NULL` removes the
+/// engine's token.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `token` must be a live token or `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_set_cancel_token(
+    engine: *mut EtEngine,
+    token: *const EtCancelToken,
+) {
+    let token = token.as_ref().map(|token| token.token.clone());
+
+    (*engine).context.set_cancel_token(token);
+}
+
+/// Registers `callback` as a function named `name` that accepts `arity_min` to `arity_max`
+/// arguments (use 255 for no maximum). `user_data` is passed to every call. Returns `false` if
+/// `name` is not valid UTF-8 or is a built-in's, or if `arity_min` is above `arity_max`.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `name` must be a NUL-terminated string. `user_data`
+/// must remain valid for as long as the function stays registered, and the callback must be
+/// safe to call from whichever threads render with the engine.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_register_fn(
+    engine: *mut EtEngine,
+    name: *const c_char,
+    arity_min: u8,
+    arity_max: u8,
+    callback: EtCallback,
+    user_data: *mut c_void,
+) -> bool {
+    let Some(name) = str_from(name) else {
+        return false;
+    };
+    let user_data = UserData(user_data);
+
+    (*engine)
+        .engine
+        .register_fn(name, arity_min..=arity_max, move |args| {
+            call(callback, user_data.get(), args)
+        })
+        .is_ok()
+}
+
+/// # Safety
+///
+/// `engine` must be a live engine, and `name` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_unregister_fn(
+    engine: *mut EtEngine,
+    name: *const c_char,
+) -> bool {
+    match str_from(name) {
+        Some(name) => (*engine).engine.unregister_fn(name),
+        None => false,
+    }
+}
+
+/// Renders `source` with the engine's attributes. The result must be freed with
+/// `etscript_free_render_result`.
+///
+/// # Safety
+///
+/// `engine` must be a live engine, and `source` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_engine_render(
+    engine: *const EtEngine,
+    source: *const c_char,
+) -> *mut EtRenderResult {
+    let result = match CStr::from_ptr(source).to_str() {
+        Ok(source) => (*engine).engine.render_with(source, &(*engine).context),
+        Err(err) => Err(EtError::new(
+            Status::InputError,
+            ErrorKind::Input,
+            err.to_string(),
+        )),
+    };
+
+    render_result(result)
+}
+
+/// # Safety
+///
+/// `ptr` must have been returned by `etscript_engine_render` and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_free_render_result(ptr: *mut EtRenderResult) {
+    if ptr.is_null() {
+        return;
+    }
+    let result = Box::from_raw(ptr);
+
+    drop(CString::from_raw(result.output));
+    if !result.error.is_null() {
+        drop(CString::from_raw(result.error));
+    }
+    if !result.function.is_null() {
+        drop(CString::from_raw(result.function));
+    }
+    if result.diagnostics.is_null() {
+        return;
+    }
+
+    let diagnostics = Box::from_raw(ptr::slice_from_raw_parts_mut(
+        result.diagnostics,
+        result.diagnostic_count,
+    ));
+    for diagnostic in diagnostics.iter() {
+        drop(CString::from_raw(diagnostic.message));
+    }
+}
+
+//
+
+#[no_mangle]
+pub extern "C" fn etscript_cancel_token_new() -> *mut EtCancelToken {
+    Box::into_raw(Box::new(EtCancelToken {
+        token: CancelToken::new(),
+    }))
+}
+
+/// Can be called from any thread, including while a render is running.
+///
+/// # Safety
+///
+/// `token` must be a live token.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_cancel_token_cancel(token: *const EtCancelToken) {
+    (*token).token.cancel();
+}
+
+/// Engines that were given the token keep their own reference to it, so it can be freed at
+/// any time.
+///
+/// # Safety
+///
+/// `token` must have been returned by `etscript_cancel_token_new` and not freed already.
+#[no_mangle]
+pub unsafe extern "C" fn etscript_cancel_token_free(token: *mut EtCancelToken) {
+    if !token.is_null() {
+        drop(Box::from_raw(token));
+    }
+}
+
+//
+
+/// The host promises that `user_data` can be used from any thread that renders.
+#[derive(Clone, Copy)]
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    fn get(self) -> *mut c_void {
+        self.0
+    }
+}
+
+unsafe fn call(
+    callback: EtCallback,
+    user_data: *mut c_void,
+    args: &[HostValue],
+) -> Result<HostValue, String> {
+    // keeps argument strings alive until the callback returns
+    let strings = args
+        .iter()
+        .map(|arg| match arg.as_str() {
+            Some(string) => CString::new(string).map_err(|err| err.to_string()),
+            None => Ok(CString::default()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let c_args = args
+        .iter()
+        .zip(&strings)
+        .map(|(arg, string)| match arg {
+            HostValue::Number(num) => EtValue::number(*num),
+            HostValue::Boolean(boolean) => EtValue::boolean(*boolean),
+            HostValue::String(_) => EtValue::string(string.as_ptr()),
+            _ => EtValue::null(),
+        })
+        .collect::<Vec<_>>();
+    let mut out = EtValue::null();
+
+    let is_ok = callback(c_args.as_ptr(), c_args.len(), &mut out, user_data);
+    let out_string = str_from(out.string).map(str::to_owned);
+
+    if !is_ok {
+        return Err(out_string.unwrap_or_else(|| "Callback failed.".to_owned()));
+    }
+
+    let kind = EtValueKind::try_from(out.kind)
+        .map_err(|kind| format!("Callback returned a value of unknown kind: {kind}."))?;
+
+    Ok(match kind {
+        EtValueKind::Null => HostValue::Null,
+        EtValueKind::Number => HostValue::Number(out.number),
+        EtValueKind::Boolean => HostValue::Boolean(out.boolean),
+        EtValueKind::String => match out_string {
+            Some(string) => HostValue::String(string),
+            None => return Err("Callback returned a string that is not valid UTF-8.".to_owned()),
+        },
+    })
+}
+
+impl EtValue {
+    fn null() -> Self {
+        Self {
+            kind: EtValueKind::Null as u32,
+            number: 0.0,
+            boolean: false,
+            string: ptr::null(),
+        }
+    }
+
+    fn number(num: f64) -> Self {
+        Self {
+            kind: EtValueKind::Number as u32,
+            number: num,
+            ..Self::null()
+        }
+    }
+
+    fn boolean(boolean: bool) -> Self {
+        Self {
+            kind: EtValueKind::Boolean as u32,
+            boolean,
+            ..Self::null()
+        }
+    }
+
+    fn string(string: *const c_char) -> Self {
+        Self {
+            kind: EtValueKind::String as u32,
+            string,
+            ..Self::null()
+        }
+    }
+}
+
+//
+
+#[repr(C)]
+pub struct InterpretResult {
+    pub value: *mut c_char,
+    /// An `EtStatus`.
+    pub status: i32,
+}
+
+/// # Safety
+///
+/// We can dance if we want to.
+#[no_mangle]
+pub unsafe extern "C" fn interpret(input: *const c_char) -> *mut InterpretResult {
+    let result = match CStr::from_ptr(input).to_str() {
+        Ok(source) => Engine::new().render(source),
+        Err(err) => Err(EtError::new(
+            Status::InputError,
+            ErrorKind::Input,
+            err.to_string(),
+        )),
+    };
+    let (status, string) = match result {
+        Ok(rendered) => (Status::Ok, rendered.output),
+        // callers of `interpret` have always told a runtime error from output by its prefix
+        Err(err) if err.status() == Status::RuntimeError => (err.status(), format!("Error: {err}")),
+        Err(err) => (err.status(), err.to_string()),
+    };
+
+    let result = InterpretResult {
+        value: c_string(string),
+        status: status as i32,
+    };
+
+    Box::into_raw(Box::new(result))
+}
+
+/// Frees the result along with its `value`.
+///
+/// # Safety
+///
+/// We can leave your friends behind.
+#[no_mangle]
+pub unsafe extern "C" fn free_result(ptr: *mut InterpretResult) {
+    if ptr.is_null() {
+        return;
+    }
+    let result = Box::from_raw(ptr);
+
+    if !result.value.is_null() {
+        drop(CString::from_raw(result.value));
+    }
+}
@@ -7,12 +7,13 @@ pub mod math;
 pub mod string;
 pub mod utilities;
 
-use crate::object;
+use crate::object::{Interner, Symbol};
 use crate::value::Value;
 use crate::vm::Vm;
+use crate::ErrorKind;
 
 use libc::{c_char, c_int, c_longlong};
-use std::ffi::CStr;
+use std::{collections::HashMap, ffi::CStr};
 
 #[repr(C)]
 pub struct NResult {
@@ -33,14 +34,17 @@ impl NResult {
             let status = (*ptr).status;
 
             let Ok(result) = value.to_str() else {
-                return Err(Value::error("Invalid UTF-8 string.".to_owned()));
+                return Err(Value::error_of(
+                    ErrorKind::DotNet,
+                    "Invalid UTF-8 string.".to_owned(),
+                ));
             };
 
             let string = result.to_owned();
             free_n_result(ptr);
 
             if status != 0 {
-                return Err(Value::error(string));
+                return Err(Value::error_of(ErrorKind::DotNet, string));
             }
 
             Ok((string, data))
@@ -50,116 +54,121 @@ impl NResult {
 
 //
 
-pub fn init(vm: &mut Vm) {
-    let define_fn = |name: &str, nfn, vm: &mut Vm| {
-        let hash = object::intern(name.to_owned(), vm);
-        vm.funcs.insert(hash, Value::nfn(hash, nfn));
+pub fn init(funcs: &mut HashMap<Symbol, Value>, strings: &mut Interner) {
+    let mut define_fn = |name: &str, nfn, funcs: &mut HashMap<Symbol, Value>| {
+        let symbol = strings.intern(name);
+        funcs.insert(symbol, Value::nfn(symbol, nfn));
     };
 
     // Content
     define_fn(
         "buildrowsetfromstring",
         content::build_rowset_from_string,
-        vm,
+        funcs,
     );
 
     // Data Extension
     define_fn(
         "dataextensionrowcount",
         data_extension::data_extension_row_count,
-        vm,
+        funcs,
+    );
+    define_fn("deletedata", data_extension::delete_data, funcs);
+    define_fn("deletede", data_extension::delete_de, funcs);
+    define_fn("field", data_extension::field, funcs);
+    define_fn("insertdata", data_extension::insert_data, funcs);
+    define_fn("insertde", data_extension::insert_de, funcs);
+    define_fn("lookup", data_extension::lookup, funcs);
+    define_fn(
+        "lookuporderedrows",
+        data_extension::lookup_ordered_rows,
+        funcs,
     );
-    define_fn("deletedata", data_extension::delete_data, vm);
-    define_fn("deletede", data_extension::delete_de, vm);
-    define_fn("field", data_extension::field, vm);
-    define_fn("insertdata", data_extension::insert_data, vm);
-    define_fn("insertde", data_extension::insert_de, vm);
-    define_fn("lookup", data_extension::lookup, vm);
-    define_fn("lookuporderedrows", data_extension::lookup_ordered_rows, vm);
     define_fn(
         "lookuporderedrowscs",
         data_extension::lookup_ordered_rows_cs,
-        vm,
+        funcs,
     );
-    define_fn("lookuprows", data_extension::lookup_rows, vm);
-    define_fn("lookuprowscs", data_extension::lookup_rows_cs, vm);
-    define_fn("row", data_extension::row, vm);
-    define_fn("rowcount", data_extension::row_count, vm);
-    define_fn("updatedata", data_extension::update_data, vm);
-    define_fn("updatede", data_extension::update_de, vm);
-    define_fn("upsertdata", data_extension::upsert_data, vm);
-    define_fn("upsertde", data_extension::upsert_de, vm);
+    define_fn("lookuprows", data_extension::lookup_rows, funcs);
+    define_fn("lookuprowscs", data_extension::lookup_rows_cs, funcs);
+    define_fn("row", data_extension::row, funcs);
+    define_fn("rowcount", data_extension::row_count, funcs);
+    define_fn("updatedata", data_extension::update_data, funcs);
+    define_fn("updatede", data_extension::update_de, funcs);
+    define_fn("upsertdata", data_extension::upsert_data, funcs);
+    define_fn("upsertde", data_extension::upsert_de, funcs);
 
     // Date & Time (.NET)
-    define_fn("dateadd", datetime::date_add, vm);
-    define_fn("datediff", datetime::date_diff, vm);
-    define_fn("dateparse", datetime::date_parse, vm);
-    define_fn("datepart", datetime::date_part, vm);
-    define_fn("formatdate", datetime::format_date, vm);
+    define_fn("dateadd", datetime::date_add, funcs);
+    define_fn("datediff", datetime::date_diff, funcs);
+    define_fn("dateparse", datetime::date_parse, funcs);
+    define_fn("datepart", datetime::date_part, funcs);
+    define_fn("formatdate", datetime::format_date, funcs);
     define_fn(
         "localdatetosystemdate",
         datetime::local_date_to_system_date,
-        vm,
+        funcs,
     );
-    define_fn("now", datetime::now, vm);
+    define_fn("now", datetime::now, funcs);
     define_fn(
         "systemdatetolocaldate",
         datetime::system_date_to_local_date,
-        vm,
+        funcs,
     );
 
     // Encoding
-    define_fn("base64decode", encoding::base64_decode, vm);
-    define_fn("base64encode", encoding::base64_encode, vm);
-    define_fn("guid", encoding::guid, vm);
+    define_fn("base64decode", encoding::base64_decode, funcs);
+    define_fn("base64encode", encoding::base64_encode, funcs);
+    define_fn("guid", encoding::guid, funcs);
 
     // Encryption
-    define_fn("md5", encryption::md5, vm);
-    define_fn("sha1", encryption::sha1, vm);
-    define_fn("sha256", encryption::sha256, vm);
-    define_fn("sha512", encryption::sha512, vm);
+    define_fn("md5", encryption::md5, funcs);
+    define_fn("sha1", encryption::sha1, funcs);
+    define_fn("sha256", encryption::sha256, funcs);
+    define_fn("sha512", encryption::sha512, funcs);
 
     // Math
-    define_fn("add", math::add, vm);
-    define_fn("divide", math::divide, vm);
-    define_fn("formatcurrency", math::format_currency, vm); // .NET
-    define_fn("formatnumber", math::format_number, vm); // .NET
-    define_fn("mod", math::mod_, vm);
-    define_fn("multiply", math::multiply, vm);
-    define_fn("random", math::random, vm);
-    define_fn("subtract", math::subtract, vm);
+    define_fn("add", math::add, funcs);
+    define_fn("divide", math::divide, funcs);
+    define_fn("formatcurrency", math::format_currency, funcs); // .NET
+    define_fn("formatnumber", math::format_number, funcs); // .NET
+    define_fn("mod", math::mod_, funcs);
+    define_fn("multiply", math::multiply, funcs);
+    define_fn("random", math::random, funcs);
+    define_fn("subtract", math::subtract, funcs);
 
     // String
-    define_fn("char", string::char_, vm);
-    define_fn("concat", string::concat, vm);
-    define_fn("format", string::format, vm); // .NET
-    define_fn("indexof", string::index_of, vm);
-    define_fn("length", string::length, vm);
-    define_fn("lowercase", string::lowercase, vm);
-    define_fn("propercase", string::proper_case, vm); // .NET
-    define_fn("regexmatch", string::regex_match, vm); // .NET
-    define_fn("replace", string::replace, vm);
-    define_fn("replacelist", string::replace_list, vm);
-    define_fn("stringtodate", string::string_to_date, vm); // .NET
-    define_fn("stringtohex", string::string_to_hex, vm);
-    define_fn("substring", string::substring, vm);
-    define_fn("trim", string::trim, vm);
-    define_fn("uppercase", string::uppercase, vm);
+    define_fn("char", string::char_, funcs);
+    define_fn("concat", string::concat, funcs);
+    define_fn("format", string::format, funcs); // .NET
+    define_fn("indexof", string::index_of, funcs);
+    define_fn("length", string::length, funcs);
+    define_fn("lowercase", string::lowercase, funcs);
+    define_fn("propercase", string::proper_case, funcs); // .NET
+    define_fn("regexmatch", string::regex_match, funcs); // .NET
+    define_fn("replace", string::replace, funcs);
+    define_fn("replacelist", string::replace_list, funcs);
+    define_fn("stringtodate", string::string_to_date, funcs); // .NET
+    define_fn("stringtohex", string::string_to_hex, funcs);
+    define_fn("substring", string::substring, funcs);
+    define_fn("trim", string::trim, funcs);
+    define_fn("uppercase", string::uppercase, funcs);
 
     // Utilities
-    define_fn("empty", utilities::empty, vm);
-    define_fn("iif", utilities::iif, vm);
-    define_fn("isemailaddress", utilities::is_email_address, vm);
-    define_fn("isnull", utilities::is_null, vm);
+    define_fn("attributevalue", utilities::attribute_value, funcs);
+    define_fn("empty", utilities::empty, funcs);
+    define_fn("iif", utilities::iif, funcs);
+    define_fn("isemailaddress", utilities::is_email_address, funcs);
+    define_fn("isnull", utilities::is_null, funcs);
     // `output` and `outputline` are handled at compile time
-    define_fn("v", utilities::v, vm);
+    define_fn("v", utilities::v, funcs);
 }
 
 //
 
 pub fn check_arity(arity: u8, arg_count: u8) -> Result<(), Value> {
     if arg_count != arity {
-        return Err(Value::error(format!("Unexpected number of arguments passed to function — got {arg_count}, but expected {arity}.")));
+        return Err(Value::error_of(ErrorKind::Arity, format!("Unexpected number of arguments passed to function — got {arg_count}, but expected {arity}.")));
     }
 
     Ok(())
@@ -167,7 +176,7 @@ pub fn check_arity(arity: u8, arg_count: u8) -> Result<(), Value> {
 
 pub fn check_arity_min(arity_min: u8, arg_count: u8) -> Result<(), Value> {
     if arg_count < arity_min {
-        return Err(Value::error(format!("Unexpected number of arguments passed to function — got {arg_count}, but expected at least {arity_min}.")));
+        return Err(Value::error_of(ErrorKind::Arity, format!("Unexpected number of arguments passed to function — got {arg_count}, but expected at least {arity_min}.")));
     }
 
     Ok(())
@@ -175,7 +184,7 @@ pub fn check_arity_min(arity_min: u8, arg_count: u8) -> Result<(), Value> {
 
 pub fn check_arity_max(arity_max: u8, arg_count: u8) -> Result<(), Value> {
     if arg_count > arity_max {
-        return Err(Value::error(format!("Unexpected number of arguments passed to function — got {arg_count}, but expected {arity_max} at most.")));
+        return Err(Value::error_of(ErrorKind::Arity, format!("Unexpected number of arguments passed to function — got {arg_count}, but expected {arity_max} at most.")));
     }
 
     Ok(())
@@ -183,7 +192,7 @@ pub fn check_arity_max(arity_max: u8, arg_count: u8) -> Result<(), Value> {
 
 pub fn check_arity_range(arity_min: u8, arity_max: u8, arg_count: u8) -> Result<(), Value> {
     if arg_count < arity_min || arg_count > arity_max {
-        return Err(Value::error(format!("Unexpected number of arguments passed to function — got {arg_count}, but expected at least {arity_min}, {arity_max} at most.")));
+        return Err(Value::error_of(ErrorKind::Arity, format!("Unexpected number of arguments passed to function — got {arg_count}, but expected at least {arity_min}, {arity_max} at most.")));
     }
 
     Ok(())
@@ -213,10 +222,13 @@ pub fn check_arg_types(arg_types: &[ArgType], arg_start: usize, vm: &Vm) -> Resu
             ArgType::Value => ("value", true),
         };
         if !result.1 {
-            return Err(Value::error(format!(
-                "Unexpected type passed to function — expected a {}.",
-                result.0
-            )));
+            return Err(Value::error_of(
+                ErrorKind::Type,
+                format!(
+                    "Unexpected type passed to function — expected a {}.",
+                    result.0
+                ),
+            ));
         }
         offset += 1
     }
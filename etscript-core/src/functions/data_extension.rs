@@ -1,8 +1,8 @@
 use crate::db::{self, AppendType, Table, NO_CASE};
 use crate::functions::{self, ArgType};
-use crate::object;
 use crate::value::Value;
 use crate::vm::Vm;
+use crate::ErrorKind;
 
 use crate::object::StringObj;
 use rusqlite::{Result, ToSql};
@@ -263,14 +263,17 @@ pub fn field(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Valu
     let string;
     let key = if name.is_string() {
         let obj: StringObj = (*name).clone().into();
-        string = obj.get(&vm.strings.borrow()).to_owned();
-        obj.hash
+        string = obj.get(&vm.strings).to_owned();
+        // row keys are folded the same way when the row is created
+        vm.strings.lookup(&vm.fold_name(&string))
     } else if name.is_num() {
         let num = name.to_num(vm);
         string = format!("{num}");
-        object::hash_of(&string)
+        // a name that was never interned can't be a field of the row
+        vm.strings.lookup(&string)
     } else {
-        return Err(Value::error(
+        return Err(Value::error_of(
+            ErrorKind::Type,
             "Unexpected type passed to function — expected a string or number.".to_owned(),
         ));
     };
@@ -283,7 +286,7 @@ pub fn field(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Valu
         true
     };
 
-    let val = if let Some(val) = row.map.get(&key) {
+    let val = if let Some(val) = key.and_then(|key| row.map.get(&key)) {
         (*val).clone()
     } else if should_err {
         return Err(Value::error(format!(
@@ -353,17 +356,18 @@ pub fn data_extension_row_count(
     functions::check_arg_types(ARG_TYPES, arg_start, vm)?;
 
     let tbl_name = stack[arg_start].to_ascii_lower(vm);
-    let table = Table::new(&tbl_name)?;
+    let table = Table::new(&tbl_name, vm)?;
 
     // table name already validated
-    let mut stmt = table
-        .db
-        .prepare(&format!("SELECT count(rowid) FROM {tbl_name}"))?;
+    let sql = format!("SELECT count(rowid) FROM {tbl_name}");
+    vm.observe_query(&sql);
+    let mut stmt = table.db.prepare(&sql)?;
 
     let Some(count): Option<f64> = stmt.query_row([], |row| row.get(0))? else {
-        return Err(Value::error(format!(
-            "Table `{}` was not found or is not configured.", table.name
-        )));
+        return Err(Value::error_of(
+            ErrorKind::Database,
+            format!("Table `{}` was not found or is not configured.", table.name),
+        ));
     };
 
     Ok(Value::num(count))
@@ -429,7 +433,8 @@ fn exec_insert(
     check_arg_pairs(arity_min, arg_count)?;
 
     let tbl_name = stack[arg_start].to_ascii_lower(vm);
-    let table = Table::new(&tbl_name)?;
+    let table = Table::new(&tbl_name, vm)?;
+    let stack = &vm.stack;
 
     let insert_col_offset = arg_start + 1;
     let insert_col_count = (stack.len() - insert_col_offset) / 2;
@@ -504,15 +509,16 @@ fn exec_select(
     check_arg_pairs(arity_min, arg_count)?;
 
     let tbl_name = stack[arg_start].to_ascii_lower(vm);
-    let table = Table::new(&tbl_name)?;
+    let table = Table::new(&tbl_name, vm)?;
+    let stack = &vm.stack;
 
-    let result_col_hash = if sel_context == Select::Row {
-        let (name, hash) = stack[arg_start + 1].to_string_hash(vm);
+    let result_col = if sel_context == Select::Row {
+        let name = stack[arg_start + 1].to_string(vm);
         let _ = &table.col_position(&name)?; // error if not found
 
-        hash
+        name
     } else {
-        0 // never used
+        String::with_capacity(0) // never used
     };
 
     let result_limit = match sel_context {
@@ -612,19 +618,20 @@ WHERE {filter_col} = ?1 {collation}"
     let rows = db::exec_stmt(stmt, params.as_slice(), vm)?;
 
     let val = if sel_context == Select::Row {
-        let mut result_col_val = Value::null();
-
-        if !rows.is_empty() {
-            let row = rows[0].to_row();
-
-            if let Some(val) = row.map.get(&result_col_hash) {
-                if !val.is_null() {
-                    result_col_val = (*val).clone();
-                }
-            }
-        }
+        let Some(row) = rows.first().map(Value::to_row) else {
+            return Ok(Value::null());
+        };
+        // row keys are folded the same way in `exec_stmt()`, so a strict engine only finds a
+        // column spelled as it is in the table
+        let key = vm.strings.lookup(&vm.fold_name(&result_col));
+        let Some(val) = key.and_then(|key| row.map.get(&key)) else {
+            return Err(Value::error_of(
+                ErrorKind::Database,
+                format!("No such column: {result_col}"),
+            ));
+        };
 
-        result_col_val
+        (*val).clone()
     } else {
         Value::rowset(rows)
     };
@@ -649,7 +656,8 @@ fn exec_update(
     let is_upsert = upd_context == Update::UpsertSend || upd_context == Update::UpsertReq;
 
     let tbl_name = stack[arg_start].to_ascii_lower(vm);
-    let table = Table::new(&tbl_name)?;
+    let table = Table::new(&tbl_name, vm)?;
+    let stack = &vm.stack;
 
     let filter_col_offset = arg_start + 2;
     let filter_col_count = f64::trunc(stack[filter_col_offset - 1].to_num(vm)) as usize;
@@ -826,7 +834,8 @@ fn exec_delete(
     check_arg_pairs(arity_min, arg_count)?;
 
     let tbl_name = stack[arg_start].to_ascii_lower(vm);
-    let table = Table::new(&tbl_name)?;
+    let table = Table::new(&tbl_name, vm)?;
+    let stack = &vm.stack;
 
     let filter_col_offset = arg_start + 1;
     let filter_col = stack[filter_col_offset].to_string(vm);
@@ -3,7 +3,7 @@ use crate::value::Value;
 use crate::vm::Vm;
 use std::ffi::CString;
 
-use libc::{c_char, c_int};
+use libc::{c_char, c_int, c_longlong};
 
 extern "C" {
     fn n_date_add(date: *const c_char, addend: c_int, unit: c_int) -> *const NResult;
@@ -30,6 +30,8 @@ extern "C" {
     fn n_now() -> *const NResult; // parameter for send time preservation omitted
 
     fn n_system_date_to_local_date(date: *const c_char) -> *const NResult;
+
+    fn n_system_time_from_unix_time(unix_time: c_longlong) -> *const NResult;
 }
 
 //
@@ -85,7 +87,9 @@ pub fn date_diff(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value,
         ))?;
 
         let Ok(num) = string.0.parse::<f64>() else {
-            return Err(Value::error("Invalid string representation of a date or time.".to_owned()));
+            return Err(Value::error(
+                "Invalid string representation of a date or time.".to_owned(),
+            ));
         };
 
         Ok(Value::num(num))
@@ -228,7 +232,10 @@ pub fn now(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Value>
     }
 
     unsafe {
-        let dt = NResult::consume(n_now())?;
+        let dt = match vm.clock.fixed_unix_millis() {
+            Some(unix_time) => NResult::consume(n_system_time_from_unix_time(unix_time))?,
+            None => NResult::consume(n_now())?,
+        };
 
         Ok(Value::datetime(dt, vm))
     }
@@ -296,3 +303,15 @@ fn date_unit_id(date_unit: &str) -> Result<c_int, Value> {
 
     Ok(id)
 }
+
+/// The Unix time (in milliseconds) of `date`, parsed as `DateParse()` would, e.g., so that a date
+/// can be compared with a string.
+pub fn parse_unix_millis(date: &str) -> Option<i64> {
+    let date = CString::new(date).ok()?;
+
+    unsafe {
+        let (_, unix_millis) = NResult::consume(n_date_parse(date.as_ptr(), 0)).ok()?;
+
+        Some(unix_millis)
+    }
+}
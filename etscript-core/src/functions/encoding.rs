@@ -3,7 +3,8 @@ use crate::value::Value;
 use crate::vm::Vm;
 
 use base64::{engine::general_purpose, Engine as _};
-use uuid::Uuid;
+use rand::Rng;
+use uuid::Builder;
 
 pub fn base64_decode(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Value> {
     const ARITY_MIN: u8 = 1;
@@ -99,7 +100,8 @@ pub fn guid(_arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Valu
     const ARITY: u8 = 0; // -> String
     functions::check_arity(ARITY, arg_count)?;
 
-    let val = Value::string(Uuid::new_v4().to_string(), vm);
+    let guid = Builder::from_random_bytes(vm.rng.gen()).into_uuid();
+    let val = Value::string(guid.to_string(), vm);
 
     Ok(val)
 }
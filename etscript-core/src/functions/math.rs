@@ -189,7 +189,7 @@ pub fn random(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Val
     let a = stack[arg_start].to_num(vm);
     let b = stack[arg_start + 1].to_num(vm);
 
-    let result = f64::trunc(rand::thread_rng().gen_range(a..=b));
+    let result = f64::trunc(vm.rng.gen_range(a..=b));
 
     Ok(Value::num(result))
 }
@@ -257,7 +257,9 @@ pub fn regex_match(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value
     let group = if num_arg.1.is_some() {
         let string = num_arg.1.unwrap();
         let Ok(c_string) = CString::new(string) else {
-            return Err(Value::error("Interior nul byte (`\0`) was found.".to_owned()));
+            return Err(Value::error(
+                "Interior nul byte (`\0`) was found.".to_owned(),
+            ));
         };
         c_string
     } else {
@@ -405,7 +407,9 @@ pub fn string_to_hex(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Val
     use std::fmt::Write as _;
     for b in bytes {
         let Ok(_) = write!(hex_string, "{b:02x}") else {
-            return Err(Value::error(format!("Unable to create hex string from `{input}`")));
+            return Err(Value::error(format!(
+                "Unable to create hex string from `{input}`"
+            )));
         };
     }
 
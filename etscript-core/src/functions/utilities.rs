@@ -4,6 +4,28 @@ use crate::vm::Vm;
 
 use regex::Regex;
 
+pub fn attribute_value(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Value> {
+    const ARITY: u8 = 1;
+    const ARG_TYPES: &[ArgType] = &[
+        ArgType::String, // attribute name
+                         // -> String/Null
+    ];
+    let stack = &vm.stack;
+    functions::check_arity(ARITY, arg_count)?;
+    functions::check_arg_types(ARG_TYPES, arg_start, vm)?;
+
+    let symbol = vm.strings.lookup(&stack[arg_start].to_ascii_lower(vm));
+
+    // unlike `%%attribute%%`, a missing attribute is not an error
+    let val = if let Some(val) = symbol.and_then(|symbol| vm.attrs.get(&symbol)) {
+        val.clone()
+    } else {
+        Value::null()
+    };
+
+    Ok(val)
+}
+
 pub fn empty(arg_start: usize, arg_count: u8, vm: &mut Vm) -> Result<Value, Value> {
     const ARITY: u8 = 1;
     const ARG_TYPES: &[ArgType] = &[
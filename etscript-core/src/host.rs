@@ -0,0 +1,167 @@
+use crate::functions;
+use crate::object::{self, HostFnObj, ObjType, Symbol};
+use crate::value::{Value, ValueType};
+use crate::vm::Vm;
+use crate::ErrorKind;
+
+use std::collections::{BTreeMap, HashMap};
+
+/// A function defined by the host. Returning `Err` stops the render with the given message.
+pub type HostFn = dyn Fn(&[HostValue]) -> Result<HostValue, String> + Send + Sync;
+
+/// A value passed between ETscript and the host.
+#[derive(Clone, Debug, PartialEq)]
+pub enum HostValue {
+    Null,
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    /// Field names are lowercase unless the engine is strict.
+    Row(BTreeMap<String, HostValue>),
+    Rowset(Vec<BTreeMap<String, HostValue>>),
+}
+
+impl HostValue {
+    pub fn is_null(&self) -> bool {
+        matches!(self, HostValue::Null)
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            HostValue::Number(num) => Some(*num),
+            HostValue::String(string) => string.parse::<f64>().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            HostValue::Boolean(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            HostValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn from_value(val: &Value, vm: &Vm) -> Self {
+        match &val.type_ {
+            ValueType::Null(_) => HostValue::Null,
+            ValueType::Number(num) => HostValue::Number(*num),
+            ValueType::Boolean(boolean) => HostValue::Boolean(*boolean),
+            ValueType::Obj(obj) => match &obj.type_ {
+                ObjType::String(_) => HostValue::String(val.to_string(vm)),
+                ObjType::Row(row_obj) => HostValue::Row(Self::from_row(&row_obj.map, vm)),
+                ObjType::Rowset(rs_obj) => HostValue::Rowset(
+                    rs_obj
+                        .vec
+                        .iter()
+                        .map(|row_val| Self::from_row(&row_val.to_row().map, vm))
+                        .collect(),
+                ),
+                ObjType::NativeFn(_) | ObjType::HostFn(_) | ObjType::Error(_) => HostValue::Null,
+            },
+        }
+    }
+
+    fn from_row(map: &HashMap<Symbol, Value>, vm: &Vm) -> BTreeMap<String, HostValue> {
+        map.iter()
+            .filter_map(|(symbol, val)| {
+                let name = vm.strings.get(*symbol)?.to_owned();
+                Some((name, Self::from_value(val, vm)))
+            })
+            .collect()
+    }
+
+    pub(crate) fn into_value(self, vm: &mut Vm) -> Value {
+        match self {
+            HostValue::Null => Value::null(),
+            HostValue::Number(num) => Value::num(num),
+            HostValue::Boolean(boolean) => Value::boolean(boolean),
+            HostValue::String(string) => Value::string(string, vm),
+            HostValue::Row(map) => Self::into_row(map, vm),
+            HostValue::Rowset(vec) => {
+                Value::rowset(vec.into_iter().map(|map| Self::into_row(map, vm)).collect())
+            }
+        }
+    }
+
+    fn into_row(map: BTreeMap<String, HostValue>, vm: &mut Vm) -> Value {
+        let mut row = HashMap::new();
+
+        for (name, host_val) in map {
+            let symbol = object::intern(vm.fold_name(&name), vm);
+            let val = host_val.into_value(vm);
+            row.insert(symbol, val);
+        }
+
+        Value::row(row)
+    }
+}
+
+impl From<f64> for HostValue {
+    fn from(num: f64) -> Self {
+        HostValue::Number(num)
+    }
+}
+
+impl From<bool> for HostValue {
+    fn from(boolean: bool) -> Self {
+        HostValue::Boolean(boolean)
+    }
+}
+
+impl From<&str> for HostValue {
+    fn from(string: &str) -> Self {
+        HostValue::String(string.to_owned())
+    }
+}
+
+impl From<String> for HostValue {
+    fn from(string: String) -> Self {
+        HostValue::String(string)
+    }
+}
+
+impl From<BTreeMap<String, HostValue>> for HostValue {
+    fn from(map: BTreeMap<String, HostValue>) -> Self {
+        HostValue::Row(map)
+    }
+}
+
+impl From<Vec<BTreeMap<String, HostValue>>> for HostValue {
+    fn from(vec: Vec<BTreeMap<String, HostValue>>) -> Self {
+        HostValue::Rowset(vec)
+    }
+}
+
+//
+
+pub fn call(
+    hfn_obj: &HostFnObj,
+    arg_start: usize,
+    arg_count: u8,
+    vm: &mut Vm,
+) -> Result<Value, Value> {
+    match hfn_obj.arity {
+        (arity_min, arity_max) if arity_min == arity_max => {
+            functions::check_arity(arity_min, arg_count)?
+        }
+        (arity_min, u8::MAX) => functions::check_arity_min(arity_min, arg_count)?,
+        (arity_min, arity_max) => functions::check_arity_range(arity_min, arity_max, arg_count)?,
+    }
+
+    let args = vm.stack[arg_start..]
+        .iter()
+        .map(|val| HostValue::from_value(val, vm))
+        .collect::<Vec<_>>();
+
+    match (hfn_obj.hfn)(&args) {
+        Ok(host_val) => Ok(host_val.into_value(vm)),
+        Err(err) => Err(Value::error_of(ErrorKind::Host, err)),
+    }
+}
@@ -1,5 +1,6 @@
+use crate::Whitespace;
+
 use std::{
-    cmp::Ordering,
     iter::{Enumerate, Peekable},
     slice::Iter,
 };
@@ -98,20 +99,37 @@ pub enum Mode {
     Attr,
     Fn,
     Block,
+    Script,
+}
+
+// whitespace to drop from the start of the next pass-through text
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Trim {
+    None,
+    Newline,
+    All,
+}
+
+// what kind of server-side `<script>` tag starts at the current offset, and how long it is
+enum ScriptTag {
+    Ampscript(usize),
+    Other(usize),
 }
 
 pub struct Lexer<'a> {
     pub mode: Mode,
-    pub source: &'static str,
+    pub source: &'a str,
     src_iter: Peekable<Enumerate<Iter<'a, u8>>>,
     starting_offset: usize,
     current_offset: usize,
     line_num: u16,
     pub err_fmt_string: String,
+    pub whitespace: Whitespace,
+    trim: Trim,
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'static str) -> Self {
+    pub fn new(source: &'a str) -> Self {
         Self {
             mode: Mode::Pass,
             source,
@@ -120,6 +138,8 @@ impl<'a> Lexer<'a> {
             current_offset: 0,
             line_num: 1,
             err_fmt_string: String::new(),
+            whitespace: Whitespace::Preserve,
+            trim: Trim::None,
         }
     }
 
@@ -130,6 +150,7 @@ impl<'a> Lexer<'a> {
         self.current_offset = 0;
         self.line_num = 1;
         self.err_fmt_string.clear();
+        self.trim = Trim::None;
     }
 
     //
@@ -146,12 +167,191 @@ impl<'a> Lexer<'a> {
         self.is_alpha(c) || self.is_digit(c) || c == ' ' || c == '-'
     }
 
+    // `%%[First Name]%%` is an attribute, while `%%[EndIf]%%` and `%%[ set @a = 1 ]%%` are blocks
+    fn is_bracketed_attr(&self) -> bool {
+        let start = self.current_offset + 3; // skip `%%[`
+        let Some(length) = self.source[start..].find(']') else {
+            return false;
+        };
+        if !self.source[start + length..].starts_with("]%%") {
+            return false;
+        }
+
+        // an attribute's name is right up against the brackets, e.g., `%%[endif ]%%` is a block
+        let name = &self.source[start..start + length];
+        let Some(first_char) = name.chars().next() else {
+            return false;
+        };
+        if !self.is_alpha(first_char)
+            || name.ends_with(' ')
+            || !name.chars().all(|c| self.is_attr(c))
+        {
+            return false;
+        }
+
+        keyword(name).is_none()
+    }
+
+    // `<script runat="server" language="ampscript">` opens a block just like `%%[`; attribute
+    // names and values are case-insensitive and may come in any order, and client-side scripts
+    // (no `runat="server"`) are left as pass-through text
+    fn server_script_tag(&self) -> Option<ScriptTag> {
+        let tag = &self.source.as_bytes()[self.current_offset..];
+        let skip_whitespace = |mut i: usize| {
+            while i < tag.len() && tag[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            i
+        };
+        if tag.len() < 8 || !tag[..7].eq_ignore_ascii_case(b"<script") {
+            return None;
+        }
+        if !tag[7].is_ascii_whitespace() && tag[7] != b'>' {
+            return None; // e.g. `<scripts>`
+        }
+
+        let mut runat = None;
+        let mut language = None;
+        let mut i = 7;
+
+        loop {
+            i = skip_whitespace(i);
+            match tag.get(i) {
+                Some(b'>') => break,
+                None => return None, // an unterminated tag is just text
+                _ => (),
+            }
+
+            let name_start = i;
+            while i < tag.len() && !tag[i].is_ascii_whitespace() && tag[i] != b'=' && tag[i] != b'>'
+            {
+                i += 1;
+            }
+            let name = &tag[name_start..i];
+            let mut value: &[u8] = &[];
+
+            i = skip_whitespace(i);
+            if tag.get(i) == Some(&b'=') {
+                i = skip_whitespace(i + 1);
+                match tag.get(i) {
+                    Some(&quote_mark) if quote_mark == b'"' || quote_mark == b'\'' => {
+                        let length = tag[i + 1..].iter().position(|&c| c == quote_mark)?;
+                        value = &tag[i + 1..i + 1 + length];
+                        i += length + 2;
+                    }
+                    _ => {
+                        let value_start = i;
+                        while i < tag.len() && !tag[i].is_ascii_whitespace() && tag[i] != b'>' {
+                            i += 1;
+                        }
+                        value = &tag[value_start..i];
+                    }
+                }
+            }
+
+            if name.eq_ignore_ascii_case(b"runat") {
+                runat = Some(value);
+            } else if name.eq_ignore_ascii_case(b"language") {
+                language = Some(value);
+            }
+        }
+
+        if !runat.is_some_and(|value| value.eq_ignore_ascii_case(b"server")) {
+            return None;
+        }
+        if language.is_some_and(|value| value.eq_ignore_ascii_case(b"ampscript")) {
+            Some(ScriptTag::Ampscript(i + 1))
+        } else {
+            Some(ScriptTag::Other(i + 1))
+        }
+    }
+
+    // `</script>`, in any case and with optional whitespace before `>`
+    fn closing_script_tag(&self) -> Option<usize> {
+        let tag = &self.source.as_bytes()[self.current_offset..];
+        if tag.len() < 8 || !tag[..8].eq_ignore_ascii_case(b"</script") {
+            return None;
+        }
+        let length = 8 + tag[8..]
+            .iter()
+            .take_while(|c| c.is_ascii_whitespace())
+            .count();
+
+        match tag.get(length) {
+            Some(b'>') => Some(length + 1),
+            _ => None,
+        }
+    }
+
+    // `-]%%` trims every whitespace character after a block, overriding `self.whitespace`
+    fn close_block(&mut self, length: usize, is_trimmed: bool) -> Token {
+        self.advance_over_tag(length);
+        self.mode = Mode::Pass;
+        self.trim = if is_trimmed {
+            Trim::All
+        } else if self.whitespace == Whitespace::SwallowNewline {
+            Trim::Newline
+        } else {
+            Trim::None
+        };
+
+        self.create_token(TokenType::BlockDelimClose)
+    }
+
+    fn skip_trimmed_whitespace(&mut self) {
+        match self.trim {
+            Trim::None => (),
+            Trim::Newline => {
+                let rest = &self.source[self.current_offset..];
+                if rest.starts_with("\r\n") {
+                    self.advance_by(2);
+                    self.line_num += 1;
+                } else if rest.starts_with('\n') {
+                    self.advance();
+                    self.line_num += 1;
+                }
+            }
+            Trim::All => {
+                while let Some(next_char) = self.peek() {
+                    if !next_char.is_ascii_whitespace() {
+                        break;
+                    }
+                    if next_char == '\n' {
+                        self.line_num += 1;
+                    }
+                    self.advance();
+                }
+            }
+        }
+        self.trim = Trim::None;
+    }
+
+    // `%%[-` trims every whitespace character before a block
+    fn create_trimmed_pass_token(&self) -> Token {
+        let mut token = self.create_token(TokenType::Pass);
+        let text = &self.source[token.offset..token.offset + token.length];
+        token.length = text
+            .trim_end_matches(|c: char| c.is_ascii_whitespace())
+            .len();
+
+        token
+    }
+
+    fn advance_over_tag(&mut self, length: usize) {
+        let tag = &self.source[self.current_offset..self.current_offset + length];
+        self.line_num += tag.matches('\n').count() as u16;
+        self.advance_by(length);
+    }
+
     #[allow(clippy::wrong_self_convention)]
     fn is_next(&mut self, expected: char) -> bool {
         if self.is_at_end() {
             return false;
         }
-        let Some(value) = self.src_iter.next_if(|&item| item == (self.current_offset, &(expected as u8))) else {
+        let Some(value) = self
+            .src_iter
+            .next_if(|&item| item == (self.current_offset, &(expected as u8)))
+        else {
             return false;
         };
         self.current_offset = value.0 + 1;
@@ -239,116 +439,9 @@ impl<'a> Lexer<'a> {
         self.create_token(TokenType::Error)
     }
 
-    fn match_keyword(
-        &self,
-        offset: usize,
-        length: usize,
-        remainder: &str,
-        type_: TokenType,
-    ) -> TokenType {
-        if self.current_offset - self.starting_offset != offset + length {
-            return TokenType::Identifier;
-        }
-
-        let lexeme = &mut self.source.as_bytes()
-            [self.starting_offset + offset..self.current_offset]
-            .to_ascii_lowercase();
-
-        if remainder.as_bytes().iter().cmp(lexeme.iter()) == Ordering::Equal {
-            return type_;
-        }
-
-        TokenType::Identifier
-    }
-
     fn ident_or_keyword(&self) -> TokenType {
-        let Some(first_char) = self.char_at(0) else {
-            return TokenType::Identifier;
-        };
-        match first_char {
-            'A' | 'a' => self.match_keyword(1, 2, "nd", TokenType::And),
-            'D' | 'd' => match self.current_offset - self.starting_offset > 1 {
-                true => {
-                    let Some(next_char) = self.char_at(1) else {
-                        return TokenType::Identifier;
-                    };
-                    match next_char {
-                        'O' | 'o' => {
-                            if self.match_keyword(0, 2, "do", TokenType::Do) == TokenType::Do {
-                                return TokenType::Do;
-                            }
-                            self.match_keyword(2, 4, "wnto", TokenType::DownTo)
-                        }
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => TokenType::Identifier,
-            },
-            'E' | 'e' => match self.current_offset - self.starting_offset > 1 {
-                true => {
-                    let Some(next_char) = self.char_at(1) else {
-                        return TokenType::Identifier;
-                    };
-                    match next_char {
-                        'L' | 'l' => {
-                            if self.match_keyword(2, 2, "se", TokenType::Else) == TokenType::Else {
-                                return TokenType::Else;
-                            }
-                            self.match_keyword(2, 4, "seif", TokenType::ElseIf)
-                        }
-                        'N' | 'n' => self.match_keyword(2, 3, "dif", TokenType::EndIf),
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => TokenType::Identifier,
-            },
-            'F' | 'f' => match self.current_offset - self.starting_offset > 1 {
-                true => {
-                    let Some(next_char) = self.char_at(1) else {
-                        return TokenType::Identifier;
-                    };
-                    match next_char {
-                        'A' | 'a' => self.match_keyword(2, 3, "lse", TokenType::False),
-                        'O' | 'o' => self.match_keyword(2, 1, "r", TokenType::For),
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => TokenType::Identifier,
-            },
-            'I' | 'i' => self.match_keyword(1, 1, "f", TokenType::If),
-            'N' | 'n' => match self.current_offset - self.starting_offset > 1 {
-                true => {
-                    let Some(next_char) = self.char_at(1) else {
-                        return TokenType::Identifier;
-                    };
-                    match next_char {
-                        'E' | 'e' => self.match_keyword(2, 2, "xt", TokenType::Next),
-                        'O' | 'o' => self.match_keyword(2, 1, "t", TokenType::Not),
-                        'U' | 'u' => self.match_keyword(2, 2, "ll", TokenType::Null),
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => TokenType::Identifier,
-            },
-            'O' | 'o' => self.match_keyword(1, 1, "r", TokenType::Or),
-            'S' | 's' => self.match_keyword(1, 2, "et", TokenType::Set),
-            'T' | 't' => match self.current_offset - self.starting_offset > 1 {
-                true => {
-                    let Some(next_char) = self.char_at(1) else {
-                        return TokenType::Identifier;
-                    };
-                    match next_char {
-                        'H' | 'h' => self.match_keyword(2, 2, "en", TokenType::Then),
-                        'O' | 'o' => self.match_keyword(0, 2, "to", TokenType::To),
-                        'R' | 'r' => self.match_keyword(2, 2, "ue", TokenType::True),
-                        _ => TokenType::Identifier,
-                    }
-                }
-                _ => TokenType::Identifier,
-            },
-            'V' | 'v' => self.match_keyword(1, 2, "ar", TokenType::Var),
-            _ => TokenType::Identifier,
-        }
+        keyword(&self.source[self.starting_offset..self.current_offset])
+            .unwrap_or(TokenType::Identifier)
     }
 
     fn create_callable_token(&mut self, type_: TokenType) -> Option<Token> {
@@ -488,7 +581,7 @@ impl<'a> Lexer<'a> {
 
         while !self.is_at_end() {
             let Some(next_char) = self.peek() else {
-              break;
+                break;
             };
             if next_char == '\n' {
                 self.line_num += 1;
@@ -567,7 +660,7 @@ impl<'a> Lexer<'a> {
     //
 
     pub fn scan(&mut self) -> Token {
-        if self.mode == Mode::Fn || self.mode == Mode::Block {
+        if self.mode == Mode::Fn || self.mode == Mode::Block || self.mode == Mode::Script {
             if let Some(err_token) = self.skip_whitespace() {
                 return err_token; // unterminated comment
             }
@@ -596,7 +689,11 @@ impl<'a> Lexer<'a> {
                 }
                 Mode::Block => {
                     if self.peek_plus(1) == Some('%') && self.peek_plus(2) == Some('[') {
-                        self.advance_by(3);
+                        if self.peek_plus(3) == Some('-') {
+                            self.advance_by(4); // trim marker
+                        } else {
+                            self.advance_by(3);
+                        }
                         return self.create_token(TokenType::BlockDelimOpen);
                     }
                 }
@@ -606,9 +703,21 @@ impl<'a> Lexer<'a> {
                          // The succeeding `match` statement will handle this instead.
             }
         }
+        if self.mode == Mode::Script && self.peek() == Some('<') {
+            if let Some(length) = self.closing_script_tag() {
+                return self.close_block(length, false);
+            }
+            if let Some(ScriptTag::Ampscript(length)) = self.server_script_tag() {
+                self.advance_over_tag(length);
+                return self.create_token(TokenType::BlockDelimOpen);
+            }
+        }
 
         match self.mode {
             Mode::Pass => {
+                self.skip_trimmed_whitespace();
+                self.starting_offset = self.current_offset;
+
                 while !self.is_at_end() {
                     if self.peek() == Some('%') && self.peek_plus(1) == Some('%') {
                         match self.peek_plus(2) {
@@ -616,7 +725,14 @@ impl<'a> Lexer<'a> {
                                 self.mode = Mode::Fn;
                             }
                             Some('[') => {
-                                self.mode = Mode::Block;
+                                if self.is_bracketed_attr() {
+                                    self.mode = Mode::Attr;
+                                } else {
+                                    self.mode = Mode::Block;
+                                    if self.peek_plus(3) == Some('-') {
+                                        return self.create_trimmed_pass_token();
+                                    }
+                                }
                             }
                             _ => {
                                 self.mode = Mode::Attr;
@@ -624,8 +740,26 @@ impl<'a> Lexer<'a> {
                         }
                         return self.create_token(TokenType::Pass);
                     }
-                    self.advance();
-                    if self.peek() == Some('\n') {
+                    if self.peek() == Some('<') {
+                        match self.server_script_tag() {
+                            Some(ScriptTag::Ampscript(_)) => {
+                                self.mode = Mode::Script;
+                                return self.create_token(TokenType::Pass);
+                            }
+                            Some(ScriptTag::Other(length)) => {
+                                if self.current_offset > self.starting_offset {
+                                    return self.create_token(TokenType::Pass);
+                                }
+                                self.advance_over_tag(length);
+                                return self.create_err_token(
+                                    " — server-side scripts must be AMPscript: `{}` needs `language=\"ampscript\"`.",
+                                );
+                            }
+                            None => (),
+                        }
+                    }
+                    // counted once passed, so that one right after a delimiter counts too
+                    if self.advance() == Some('\n') {
                         self.line_num += 1;
                     }
                 }
@@ -648,10 +782,11 @@ impl<'a> Lexer<'a> {
                 match char_ {
                     '[' => self.create_ident_token(),
                     ']' => {
-                        if self.peek() == Some('%') && self.peek_plus(1) == Some('%') {
-                            self.advance_by(2);
-                            self.mode = Mode::Pass;
-                            self.create_token(TokenType::BlockDelimClose)
+                        if self.mode != Mode::Script
+                            && self.peek() == Some('%')
+                            && self.peek_plus(1) == Some('%')
+                        {
+                            self.close_block(2, false)
                         } else {
                             err_token
                         }
@@ -660,7 +795,17 @@ impl<'a> Lexer<'a> {
                     '(' => self.create_token(TokenType::LeftParen),
                     ')' => self.create_token(TokenType::RightParen),
                     ',' => self.create_token(TokenType::Comma),
-                    '-' => self.create_token(TokenType::Minus),
+                    '-' => {
+                        if self.mode == Mode::Block
+                            && self.peek() == Some(']')
+                            && self.peek_plus(1) == Some('%')
+                            && self.peek_plus(2) == Some('%')
+                        {
+                            self.close_block(3, true)
+                        } else {
+                            self.create_token(TokenType::Minus)
+                        }
+                    }
                     '=' => {
                         if self.peek() == Some('%') && self.peek_plus(1) == Some('%') {
                             self.advance_by(2);
@@ -692,3 +837,119 @@ impl<'a> Lexer<'a> {
         }
     }
 }
+
+// the keyword that `name` spells, ignoring case
+fn keyword(name: &str) -> Option<TokenType> {
+    // the longest keywords are `downto` and `elseif`
+    let mut buffer = [0; 6];
+    let lowercase = buffer.get_mut(..name.len())?;
+    lowercase.copy_from_slice(name.as_bytes());
+    lowercase.make_ascii_lowercase();
+
+    let type_ = match &*lowercase {
+        b"and" => TokenType::And,
+        b"do" => TokenType::Do,
+        b"downto" => TokenType::DownTo,
+        b"else" => TokenType::Else,
+        b"elseif" => TokenType::ElseIf,
+        b"endif" => TokenType::EndIf,
+        b"false" => TokenType::False,
+        b"for" => TokenType::For,
+        b"if" => TokenType::If,
+        b"next" => TokenType::Next,
+        b"not" => TokenType::Not,
+        b"null" => TokenType::Null,
+        b"or" => TokenType::Or,
+        b"set" => TokenType::Set,
+        b"then" => TokenType::Then,
+        b"to" => TokenType::To,
+        b"true" => TokenType::True,
+        b"var" => TokenType::Var,
+        _ => return None,
+    };
+
+    Some(type_)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // each token up to `Eof`, with the source it spans
+    fn scan_all(source: &str) -> Vec<(TokenType, &str)> {
+        let mut lexer = Lexer::new(source);
+        let mut tokens = Vec::new();
+
+        loop {
+            let token = lexer.scan();
+            if token.type_ == TokenType::Eof {
+                return tokens;
+            }
+            let text = &source[token.offset..token.offset + token.length];
+            tokens.push((token.type_, text));
+        }
+    }
+
+    #[test]
+    fn bracketed_attribute() {
+        assert_eq!(
+            scan_all("%%[First Name]%%"),
+            [
+                (TokenType::Pass, ""),
+                (TokenType::AttrDelim, "%%"),
+                (TokenType::Identifier, "First Name"),
+                (TokenType::AttrDelim, "%%"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keywords_in_brackets_are_blocks() {
+        for (source, keyword) in [
+            ("%%[EndIf]%%", (TokenType::EndIf, "EndIf")),
+            ("%%[endif ]%%", (TokenType::EndIf, "endif")),
+            ("%%[else ]%%", (TokenType::Else, "else")),
+        ] {
+            assert_eq!(
+                scan_all(source),
+                [
+                    (TokenType::Pass, ""),
+                    (TokenType::BlockDelimOpen, "%%["),
+                    keyword,
+                    (TokenType::BlockDelimClose, "]%%"),
+                ],
+                "{source:?}"
+            );
+        }
+    }
+
+    // an attribute's name can't have whitespace around it
+    #[test]
+    fn spaced_names_are_blocks() {
+        for source in ["%%[First Name ]%%", "%%[ First Name]%%"] {
+            assert_eq!(
+                scan_all(source),
+                [
+                    (TokenType::Pass, ""),
+                    (TokenType::BlockDelimOpen, "%%["),
+                    (TokenType::Identifier, "First"),
+                    (TokenType::Identifier, "Name"),
+                    (TokenType::BlockDelimClose, "]%%"),
+                ],
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_brackets_are_a_block() {
+        assert_eq!(
+            scan_all("%%[]%%"),
+            [
+                (TokenType::Pass, ""),
+                (TokenType::BlockDelimOpen, "%%["),
+                (TokenType::BlockDelimClose, "]%%"),
+            ]
+        );
+    }
+}
@@ -2,72 +2,695 @@ mod bytecode;
 mod compiler;
 mod db;
 mod debug;
+mod diagnostic;
+pub mod ffi;
 mod functions;
+mod host;
 mod lexer;
 mod object;
+mod observer;
 mod value;
 mod vm;
 
+pub use db::Database;
+pub use diagnostic::{Diagnostic, Severity};
+pub use host::{HostFn, HostValue};
+pub use observer::{Call, Observer};
+pub use vm::Status;
+
 use std::{
-    collections::HashMap,
-    ffi::{c_char, CStr, CString},
-    {cell::RefCell, rc::Rc},
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt,
+    io::Write,
+    ops::{Bound, RangeBounds},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
 };
-use vm::{Status, Vm};
 
-#[repr(C)]
-pub struct InterpretResult {
-    pub value: *mut c_char,
-    pub status: i32,
+use bytecode::Bytecode;
+use object::{Interner, StringTable, Symbol};
+use value::Value;
+use vm::Vm;
+
+/// Compiles and runs ETscript source code.
+///
+/// ```ignore
+/// let engine = etscript_core::Engine::new();
+/// let rendered = engine.render("%%=Add(2, 2)=%%")?;
+///
+/// assert_eq!(rendered.output, "4");
+/// ```
+pub struct Engine {
+    funcs: Arc<HashMap<Symbol, Value>>,
+    /// The names of functions, which every template's strings start from.
+    strings: Arc<Interner>,
+    database: Database,
+    limits: Limits,
+    clock: Clock,
+    seed: Option<u64>,
+    keeps_partial_output: bool,
+    is_strict: bool,
+    whitespace: Whitespace,
 }
 
-/// # Safety
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        let mut funcs = HashMap::new();
+        let mut strings = Interner::default();
+        functions::init(&mut funcs, &mut strings);
+
+        Self {
+            funcs: Arc::new(funcs),
+            strings: Arc::new(strings),
+            database: Database::default(),
+            limits: Limits::default(),
+            clock: Clock::System,
+            seed: None,
+            keeps_partial_output: true,
+            is_strict: false,
+            whitespace: Whitespace::Preserve,
+        }
+    }
+
+    /// Sets the resources each render may use. Templates compiled afterward use these limits.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets the time returned by `Now()`. Templates compiled afterward use this clock.
+    pub fn set_clock(&mut self, clock: Clock) {
+        self.clock = clock;
+    }
+
+    /// Seeds the random numbers behind `Random()` and `Guid()`. Every render starts over from
+    /// the seed, so a template renders the same output each time. `None`, the default, seeds
+    /// each render randomly.
+    pub fn set_seed(&mut self, seed: Option<u64>) {
+        self.seed = seed;
+    }
+
+    /// Whether `render_with` keeps the output rendered before an error, returning it as the
+    /// error's `partial_output`. Enabled by default.
+    pub fn set_partial_output(&mut self, is_kept: bool) {
+        self.keeps_partial_output = is_kept;
+    }
+
+    /// Whether variable names, field names, and string equality are case-sensitive. AMPscript
+    /// ignores case, which is the default; strict mode is for linting templates, so that
+    /// `@Name` and `@name` are different variables and `"A" == "a"` is false. Attribute names
+    /// are case-insensitive either way. Templates compiled afterward use this setting.
+    pub fn set_strict(&mut self, is_strict: bool) {
+        self.is_strict = is_strict;
+    }
+
+    /// Sets how the whitespace around code blocks is rendered. Templates compiled afterward use
+    /// this setting. Trim markers (`%%[-` and `-]%%`) apply regardless.
+    pub fn set_whitespace(&mut self, whitespace: Whitespace) {
+        self.whitespace = whitespace;
+    }
+
+    /// Sets the database that data extension functions read from and write to, e.g., a
+    /// `Database` or the path of a database file. Defaults to `./etscript.db`.
+    pub fn set_database(&mut self, database: impl Into<Database>) {
+        self.database = database.into();
+    }
+
+    pub fn database(&self) -> &Database {
+        &self.database
+    }
+
+    /// Defines a function that can be called like any built-in, e.g., `%%=LoyaltyTier(@id)=%%`.
+    /// Names are case-insensitive. Registering a name again replaces the function, but a
+    /// built-in's name is only free once `unregister_fn` has removed the built-in. Errors if
+    /// the name is a built-in's or if `arity` allows no number of arguments.
+    ///
+    /// ```ignore
+    /// engine.register_fn("Greet", 1..=1, |args| {
+    ///     Ok(format!("Hello, {}!", args[0].as_str().unwrap_or("friend")).into())
+    /// })?;
+    /// ```
+    pub fn register_fn<F>(
+        &mut self,
+        name: &str,
+        arity: impl RangeBounds<u8>,
+        hfn: F,
+    ) -> Result<(), EtError>
+    where
+        F: Fn(&[HostValue]) -> Result<HostValue, String> + Send + Sync + 'static,
+    {
+        let arity_min = match arity.start_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let arity_max = match arity.end_bound() {
+            Bound::Included(n) => *n,
+            Bound::Excluded(n) => n.saturating_sub(1),
+            Bound::Unbounded => u8::MAX,
+        };
+        if arity_min > arity_max {
+            return Err(EtError::new(
+                Status::InputError,
+                ErrorKind::Input,
+                format!(
+                    "`{name}` can't take at least {arity_min} and at most {arity_max} arguments."
+                ),
+            ));
+        }
+        let lowercase_name = name.to_ascii_lowercase();
+        let builtin = self
+            .strings
+            .lookup(&lowercase_name)
+            .and_then(|symbol| self.funcs.get(&symbol).filter(|func| func.is_nfn()));
+        if builtin.is_some() {
+            return Err(EtError::new(
+                Status::InputError,
+                ErrorKind::Input,
+                format!("`{name}` is a built-in function. Unregister it before replacing it."),
+            ));
+        }
+        let symbol = Arc::make_mut(&mut self.strings).intern(&lowercase_name);
+
+        Arc::make_mut(&mut self.funcs).insert(
+            symbol,
+            Value::hfn(symbol, (arity_min, arity_max), Arc::new(hfn)),
+        );
+
+        Ok(())
+    }
+
+    /// Removes a host-defined or built-in function. Returns `false` if no such function exists.
+    pub fn unregister_fn(&mut self, name: &str) -> bool {
+        let Some(symbol) = self.strings.lookup(&name.to_ascii_lowercase()) else {
+            return false;
+        };
+
+        Arc::make_mut(&mut self.funcs).remove(&symbol).is_some()
+    }
+
+    /// Compiles `source` into a `Template` that can be rendered any number of times.
+    pub fn compile(&self, source: &str) -> Result<Template, EtError> {
+        let strings = StringTable::new(Arc::clone(&self.strings));
+        let mut vm = Vm::new(strings, Arc::clone(&self.funcs));
+        vm.is_strict = self.is_strict;
+        vm.whitespace = self.whitespace;
+
+        let bc = match vm.compile(source) {
+            Ok(bc) => bc,
+            Err(status) => return Err(EtError::from_vm(status, vm)),
+        };
+
+        Ok(Template {
+            source: source.to_owned(),
+            bc: Arc::new(bc),
+            strings: vm.strings.freeze(),
+            funcs: Arc::clone(&self.funcs),
+            database: self.database.clone(),
+            limits: self.limits,
+            clock: self.clock,
+            seed: self.seed,
+            keeps_partial_output: self.keeps_partial_output,
+            is_strict: self.is_strict,
+            warnings: vm.diagnostics,
+        })
+    }
+
+    pub fn render(&self, source: &str) -> Result<Rendered, EtError> {
+        self.render_with(source, &Context::default())
+    }
+
+    pub fn render_with(&self, source: &str, context: &Context) -> Result<Rendered, EtError> {
+        self.compile(source)?.render_with(context)
+    }
+
+    /// Like `render_with`, but writes output to `out` as the program runs. See
+    /// `Template::render_to`.
+    pub fn render_to<W: Write>(
+        &self,
+        source: &str,
+        context: &Context,
+        out: &mut W,
+    ) -> Result<Variables, EtError> {
+        self.compile(source)?.render_to(context, out)
+    }
+}
+
+/// A compiled program along with the constants and strings it was compiled with.
 ///
-/// We can dance if we want to.
-#[no_mangle]
-pub unsafe extern "C" fn interpret(input: *const c_char) -> *mut InterpretResult {
-    let strings = Rc::new(RefCell::new(HashMap::new()));
-    let output = Rc::new(RefCell::new(String::new()));
-    let mut vm = Vm::new(Rc::clone(&strings), Rc::clone(&output));
-    let status;
-    let c_string; // `vm` writes to `output` regardless of status
-    let err_string = CString::new("Error: The result contains an interior NUL character.").unwrap();
-
-    match CStr::from_ptr(input).to_str() {
-        Ok(source) => {
-            match db::init() {
-                Ok(_) => {
-                    status = match vm.run(source) {
-                        Ok(_) => Status::Ok,
-                        Err(err_status) => err_status,
-                    };
-                    c_string = CString::new(output.take()).unwrap_or(err_string);
-                }
-                Err(err) => {
-                    status = Status::DatabaseError;
-                    c_string = CString::new(err.to_string()).unwrap_or(err_string);
+/// Each render starts with its own globals and output, so a `Template` can be reused for
+/// every subscriber of a send. It is also `Send + Sync`, so one template can be shared by
+/// threads rendering in parallel, e.g., through an `Arc` or scoped threads.
+pub struct Template {
+    source: String,
+    bc: Arc<Bytecode>,
+    strings: Arc<Interner>,
+    funcs: Arc<HashMap<Symbol, Value>>,
+    database: Database,
+    limits: Limits,
+    clock: Clock,
+    seed: Option<u64>,
+    keeps_partial_output: bool,
+    is_strict: bool,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Template {
+    /// The warnings found while compiling, e.g., a variable spelled two ways.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    pub fn render(&self) -> Result<Rendered, EtError> {
+        self.render_with(&Context::default())
+    }
+
+    /// Renders into memory. If an error occurs, the output rendered up to that point is
+    /// returned as the error's `partial_output` unless partial output is disabled.
+    pub fn render_with(&self, context: &Context) -> Result<Rendered, EtError> {
+        let mut output = Vec::new();
+        let result = self.render_to(context, &mut output);
+        let output = String::from_utf8(output).expect("Output should have been valid UTF-8.");
+
+        match result {
+            Ok(variables) => Ok(Rendered {
+                output,
+                variables,
+                warnings: self.warnings.clone(),
+            }),
+            Err(mut err) => {
+                if self.keeps_partial_output {
+                    err.0.partial_output = Some(output);
                 }
-            };
+                Err(err)
+            }
         }
-        Err(err) => {
-            status = Status::InputError;
-            c_string = CString::new(err.to_string()).unwrap_or(err_string);
+    }
+
+    /// Writes output to `out` as the program runs instead of collecting it first. Writes are
+    /// small and frequent, so unbuffered sinks such as files and sockets should be wrapped in
+    /// a `BufWriter`.
+    ///
+    /// Errors are returned rather than written, and anything written before one stays
+    /// written. Returns the variables as they were when the program finished.
+    pub fn render_to<W: Write>(
+        &self,
+        context: &Context,
+        out: &mut W,
+    ) -> Result<Variables, EtError> {
+        // strings interned at runtime are discarded along with `vm`
+        let strings = StringTable::new(Arc::clone(&self.strings));
+        let mut vm = Vm::new(strings, Arc::clone(&self.funcs));
+        vm.database = self.database.clone();
+        vm.limits = self.limits;
+        vm.clock = self.clock;
+        vm.is_strict = self.is_strict;
+        if let Some(seed) = self.seed {
+            vm.seed(seed);
+        }
+
+        vm.cancel_token = context.cancel_token.clone();
+        vm.observer = context.observer.clone();
+        vm.load(Arc::clone(&self.bc));
+
+        for (name, value) in &context.attributes {
+            vm.define_attr(name, value);
+        }
+        for (name, value) in &context.variables {
+            vm.define_global(name, value.clone());
         }
+
+        match vm.execute(&self.source, out) {
+            Ok(_) => Ok(vm.export_globals()),
+            Err(status) => Err(EtError::from_vm(status, vm)),
+        }
+    }
+}
+
+// fails to compile if either type stops being shareable across threads
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Engine>();
+    assert_send_sync::<Template>();
+};
+
+/// Caps on the resources a single render may use. `None` means unlimited, which is the
+/// default for each field.
+///
+/// ```ignore
+/// engine.set_limits(Limits {
+///     instructions: Some(1_000_000),
+///     wall_time: Some(Duration::from_secs(2)),
+///     ..Limits::default()
+/// });
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// Bytecode instructions executed, e.g., each iteration of a `FOR` loop takes several.
+    pub instructions: Option<u64>,
+    /// Bytes written to the output.
+    pub output_bytes: Option<u64>,
+    /// Time spent executing, checked between instructions and before each data extension
+    /// call. A single slow query is not interrupted.
+    pub wall_time: Option<Duration>,
+    /// Calls to functions that open a data extension, e.g., `Lookup` and `InsertDe`.
+    pub data_extension_calls: Option<u32>,
+}
+
+/// Where `Now()` gets the current time.
+///
+/// ```ignore
+/// // 2024-01-01 00:00:00 UTC
+/// engine.set_clock(Clock::Fixed(UNIX_EPOCH + Duration::from_secs(1_704_067_200)));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Clock {
+    /// The system's current time.
+    #[default]
+    System,
+    /// The same time for every call, e.g., for comparing renders against snapshots.
+    Fixed(SystemTime),
+}
+
+impl Clock {
+    /// Milliseconds since the Unix epoch of a fixed time, which can be negative.
+    pub(crate) fn fixed_unix_millis(&self) -> Option<i64> {
+        let Clock::Fixed(time) = self else {
+            return None;
+        };
+
+        Some(match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(since) => since.as_millis() as i64,
+            Err(err) => -(err.duration().as_millis() as i64),
+        })
+    }
+}
+
+/// How the whitespace around `%%[ ... ]%%` blocks (and server-side `<script>` tags) is
+/// rendered. Either way, `%%[-` removes the whitespace before a block and `-]%%` removes the
+/// whitespace after one.
+///
+/// ```ignore
+/// // `%%[ set @a = 1 ]%%\nHello` renders `Hello` rather than `\nHello`
+/// engine.set_whitespace(Whitespace::SwallowNewline);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Output everything outside of blocks as written, as Marketing Cloud does.
+    #[default]
+    Preserve,
+    /// Drop the newline directly after each block, so that a line holding nothing but a block
+    /// doesn't render as a blank line.
+    SwallowNewline,
+}
+
+/// Per-render data supplied by the host.
+///
+/// Attributes are the values of personalization strings such as `%%FirstName%%` or
+/// `%%[First Name]%%`. Like profile attributes and sendable data extension fields, their
+/// names are case-insensitive.
+///
+/// Variables are defined before the program runs, as if by `SET`. A `VAR` declaring one of
+/// them leaves its value as it is.
+#[derive(Clone, Default)]
+pub struct Context {
+    attributes: HashMap<String, String>,
+    variables: HashMap<String, HostValue>,
+    cancel_token: Option<CancelToken>,
+    observer: Option<Arc<dyn Observer>>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn attribute(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set_attribute(name, value);
+        self
+    }
+
+    pub fn set_attribute(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.attributes.insert(name.into(), value.into());
+    }
+
+    pub fn clear_attributes(&mut self) {
+        self.attributes.clear();
+    }
+
+    /// Defines a variable such as `@segment`. The `@` is optional, and rows and rowsets are
+    /// converted into values that `Field` and `Row` accept.
+    ///
+    /// ```ignore
+    /// let context = Context::new()
+    ///     .variable("@tier", "Gold")
+    ///     .variable("@points", 1250.0);
+    /// ```
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<HostValue>) -> Self {
+        self.set_variable(name, value);
+        self
     }
 
-    let c_string_ptr = c_string.into_raw();
-    let result = InterpretResult {
-        value: c_string_ptr,
-        status: status as i32,
-    };
+    pub fn set_variable(&mut self, name: impl Into<String>, value: impl Into<HostValue>) {
+        self.variables.insert(name.into(), value.into());
+    }
+
+    pub fn clear_variables(&mut self) {
+        self.variables.clear();
+    }
+
+    /// Stops the render with `ErrorKind::Cancelled` once `token` is cancelled.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.set_cancel_token(Some(token));
+        self
+    }
 
-    Box::into_raw(Box::new(result))
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel_token = token;
+    }
+
+    /// Notifies `observer` of the render's function calls, queries, and output.
+    pub fn observer(mut self, observer: Arc<dyn Observer>) -> Self {
+        self.set_observer(Some(observer));
+        self
+    }
+
+    pub fn set_observer(&mut self, observer: Option<Arc<dyn Observer>>) {
+        self.observer = observer;
+    }
 }
 
-/// # Safety
+/// Cancels renders from another thread. Clones share the same state.
 ///
-/// We can leave your friends behind.
-#[no_mangle]
-pub unsafe extern "C" fn free_result(ptr: *mut InterpretResult) {
-    let _result = Box::from_raw(ptr); // automatically drops when this scope ends
+/// The token is checked every thousand or so instructions and before each data extension
+/// call, so a render stops shortly after `cancel()` rather than immediately.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How far a render got before it stopped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Progress {
+    pub instructions: u64,
+    pub output_bytes: u64,
+    pub data_extension_calls: u32,
+    /// The line of the last instruction executed.
+    pub line: u32,
 }
+
+/// The output of a successful render.
+#[derive(Debug)]
+pub struct Rendered {
+    pub output: String,
+    /// The variables as they were when the program finished.
+    pub variables: Variables,
+    /// The template's warnings, as `Template::warnings()` returns them.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Variables keyed by their names, e.g., `@segment`. Names are lowercase unless the engine is
+/// strict.
+pub type Variables = BTreeMap<String, HostValue>;
+
+/// What went wrong, in more detail than a `Status`. The values are stable, and C callers
+/// receive them as `EtErrorKind`.
+#[repr(C)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The source could not be read (e.g., it is not valid UTF-8), or a host function could
+    /// not be registered.
+    Input = 1,
+    /// The lexer found something that isn't ETscript, e.g., an unterminated string.
+    Lex = 2,
+    /// The tokens don't form a valid program, e.g., a missing `ENDIF`.
+    Parse = 3,
+    UndefinedVariable = 4,
+    UndefinedAttribute = 5,
+    UndefinedFunction = 6,
+    /// A function was passed too few or too many arguments.
+    Arity = 7,
+    /// A value has the wrong type for a function or operator.
+    Type = 8,
+    /// A built-in function failed for any other reason, e.g., a row index out of range.
+    Function = 9,
+    /// A host-defined function returned an error.
+    Host = 10,
+    /// The database could not be opened or queried, or a table or column doesn't exist.
+    Database = 11,
+    /// A function implemented in .NET, e.g., `FormatDate`, failed.
+    DotNet = 12,
+    /// Output could not be written to the sink passed to `render_to`.
+    Output = 13,
+    /// The render used more of a resource than its `Limits` allow.
+    LimitExceeded = 14,
+    /// The render's `CancelToken` was cancelled.
+    Cancelled = 15,
+    /// Any other runtime error.
+    Runtime = 16,
+}
+
+/// The error that stopped compilation or a render.
+///
+/// ```ignore
+/// if let Err(err) = template.render_with(&context) {
+///     eprintln!("line {}: {} ({:?})", err.line(), err.message(), err.function());
+///     let rendered_so_far = err.partial_output().unwrap_or_default();
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct EtError(Box<ErrorDetails>);
+
+// boxed, since errors are rare and `Result`s are returned everywhere
+#[derive(Clone, Debug, PartialEq)]
+struct ErrorDetails {
+    status: Status,
+    kind: ErrorKind,
+    message: String,
+    function: Option<String>,
+    line: u32,
+    column: u32,
+    diagnostics: Vec<Diagnostic>,
+    progress: Option<Progress>,
+    partial_output: Option<String>,
+}
+
+impl EtError {
+    pub(crate) fn new(status: Status, kind: ErrorKind, message: String) -> Self {
+        Self(Box::new(ErrorDetails {
+            status,
+            kind,
+            message,
+            function: None,
+            line: 0,
+            column: 0,
+            diagnostics: Vec::new(),
+            progress: None,
+            partial_output: None,
+        }))
+    }
+
+    fn from_vm(status: Status, vm: Vm) -> Self {
+        let kind = match status {
+            Status::InputError => ErrorKind::Input,
+            Status::DatabaseError => ErrorKind::Database,
+            Status::CompileError => vm.error_kind.unwrap_or(ErrorKind::Parse),
+            Status::OutputError => ErrorKind::Output,
+            Status::LimitExceeded => ErrorKind::LimitExceeded,
+            Status::Cancelled => ErrorKind::Cancelled,
+            _ => vm.error_kind.unwrap_or(ErrorKind::Runtime),
+        };
+        // a compile error has no progress to speak of
+        let progress = (status != Status::CompileError).then(|| vm.progress());
+        let (line, column) = vm
+            .diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+            .map_or((0, 0), |diagnostic| (diagnostic.line, diagnostic.column));
+
+        Self(Box::new(ErrorDetails {
+            status,
+            kind,
+            message: vm.error,
+            function: vm.error_function,
+            line,
+            column,
+            diagnostics: vm.diagnostics,
+            progress,
+            partial_output: None,
+        }))
+    }
+
+    pub fn status(&self) -> Status {
+        self.0.status
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.0.kind
+    }
+
+    pub fn message(&self) -> &str {
+        &self.0.message
+    }
+
+    /// The function that returned the error, in lowercase, e.g., `lookup`.
+    pub fn function(&self) -> Option<&str> {
+        self.0.function.as_deref()
+    }
+
+    /// The line where the error occurred, beginning with 1. 0 means unknown.
+    pub fn line(&self) -> u32 {
+        self.0.line
+    }
+
+    /// The column where the error occurred, beginning with 1. 0 means unknown.
+    pub fn column(&self) -> u32 {
+        self.0.column
+    }
+
+    /// Every problem found, e.g., each compiler error and warning.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.0.diagnostics
+    }
+
+    /// How far a render got before it stopped.
+    pub fn progress(&self) -> Option<Progress> {
+        self.0.progress
+    }
+
+    /// The output rendered before the error. Only `render_with` keeps it, and only while
+    /// `Engine::set_partial_output` is enabled.
+    pub fn partial_output(&self) -> Option<&str> {
+        self.0.partial_output.as_deref()
+    }
+}
+
+impl fmt::Display for EtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.message)
+    }
+}
+
+impl Error for EtError {}
@@ -1,61 +1,89 @@
+use crate::host::HostFn;
 use crate::value::Value;
 use crate::vm::Vm;
+use crate::ErrorKind;
 
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
-    hash::{Hash, Hasher},
+    collections::{HashMap, HashSet},
+    ops::Index,
+    sync::Arc,
 };
 
 pub type NativeFn = fn(usize, u8, &mut Vm) -> Result<Value, Value>;
 
 #[derive(Clone, Copy)]
 pub struct NativeFnObj {
-    pub hash: u64,
+    pub symbol: Symbol,
     pub nfn: NativeFn,
 }
 
 impl NativeFnObj {
-    pub fn new(hash: u64, nfn: NativeFn) -> Self {
-        Self { hash, nfn }
+    pub fn new(symbol: Symbol, nfn: NativeFn) -> Self {
+        Self { symbol, nfn }
+    }
+}
+
+#[derive(Clone)]
+pub struct HostFnObj {
+    pub symbol: Symbol,
+    pub arity: (u8, u8),
+    pub hfn: Arc<HostFn>,
+}
+
+impl HostFnObj {
+    pub fn new(symbol: Symbol, arity: (u8, u8), hfn: Arc<HostFn>) -> Self {
+        Self { symbol, arity, hfn }
     }
 }
 
 #[derive(Clone, Copy)]
 pub struct StringObj {
-    pub hash: u64,
+    pub symbol: Symbol,
     pub data: i64,
     pub is_datetime: bool,
 }
 
 impl StringObj {
     pub fn new(string: String, vm: &mut Vm) -> Self {
-        let hash = intern(string, vm);
+        let symbol = intern(string, vm);
         let data = i64::MIN;
         let is_datetime = false;
 
         Self {
-            hash,
+            symbol,
             data,
             is_datetime,
         }
     }
 
-    pub fn get<'a>(&self, strings: &'a HashMap<u64, String>) -> &'a String {
-        &strings[&self.hash]
+    pub fn get<'a>(&self, strings: &'a StringTable) -> &'a str {
+        &strings[self.symbol]
     }
 }
 
 #[derive(Clone)]
 pub struct RowObj {
-    pub map: HashMap<u64, Value>,
+    pub map: HashMap<Symbol, Value>,
 }
 
 impl RowObj {
-    pub fn new(map: HashMap<u64, Value>) -> Self {
+    pub fn new(map: HashMap<Symbol, Value>) -> Self {
         Self { map }
     }
 }
 
+#[derive(Clone)]
+pub struct ErrorObj {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl ErrorObj {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        Self { kind, message }
+    }
+}
+
 #[derive(Clone)]
 pub struct RowsetObj {
     pub vec: Vec<Value>,
@@ -67,13 +95,15 @@ impl RowsetObj {
     }
 }
 
+// rows, rowsets, and errors are shared rather than copied, so any value is cheap to clone
 #[derive(Clone)]
 pub enum ObjType {
     NativeFn(NativeFnObj),
+    HostFn(HostFnObj),
     String(StringObj),
-    Row(RowObj),
-    Rowset(RowsetObj),
-    Error(String),
+    Row(Arc<RowObj>),
+    Rowset(Arc<RowsetObj>),
+    Error(Arc<ErrorObj>),
 }
 
 #[derive(Clone)]
@@ -89,6 +119,13 @@ impl Obj {
         false
     }
 
+    pub fn is_hfn(&self) -> bool {
+        if let ObjType::HostFn(_) = self.type_ {
+            return true;
+        }
+        false
+    }
+
     pub fn is_string(&self) -> bool {
         if let ObjType::String(_) = self.type_ {
             return true;
@@ -137,6 +174,23 @@ impl From<Obj> for NativeFnObj {
     }
 }
 
+impl From<HostFnObj> for Obj {
+    fn from(hfn_obj: HostFnObj) -> Self {
+        Self {
+            type_: ObjType::HostFn(hfn_obj),
+        }
+    }
+}
+
+impl From<Obj> for HostFnObj {
+    fn from(obj: Obj) -> Self {
+        let ObjType::HostFn(hfn_obj) = obj.type_ else {
+            panic!("Incorrect variant of `ObjType` was used. Expected `ObjType::HostFn`.");
+        };
+        hfn_obj
+    }
+}
+
 impl From<StringObj> for Obj {
     fn from(string_obj: StringObj) -> Self {
         Self {
@@ -154,15 +208,15 @@ impl From<Obj> for StringObj {
     }
 }
 
-impl From<RowObj> for Obj {
-    fn from(row_obj: RowObj) -> Self {
+impl From<Arc<RowObj>> for Obj {
+    fn from(row_obj: Arc<RowObj>) -> Self {
         Self {
             type_: ObjType::Row(row_obj),
         }
     }
 }
 
-impl From<Obj> for RowObj {
+impl From<Obj> for Arc<RowObj> {
     fn from(obj: Obj) -> Self {
         let ObjType::Row(row_obj) = obj.type_ else {
             panic!("Incorrect variant of `ObjType` was used. Expected `ObjType::Row`.");
@@ -171,15 +225,15 @@ impl From<Obj> for RowObj {
     }
 }
 
-impl From<RowsetObj> for Obj {
-    fn from(rs_obj: RowsetObj) -> Self {
+impl From<Arc<RowsetObj>> for Obj {
+    fn from(rs_obj: Arc<RowsetObj>) -> Self {
         Self {
             type_: ObjType::Rowset(rs_obj),
         }
     }
 }
 
-impl From<Obj> for RowsetObj {
+impl From<Obj> for Arc<RowsetObj> {
     fn from(obj: Obj) -> Self {
         let ObjType::Rowset(rs_obj) = obj.type_ else {
             panic!("Incorrect variant of `ObjType` was used. Expected `ObjType::Rowset`.");
@@ -190,16 +244,163 @@ impl From<Obj> for RowsetObj {
 
 //
 
-pub fn hash_of<T: Hash>(t: &T) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    t.hash(&mut hasher);
-    hasher.finish()
+/// The ID of an interned string. Symbols from the same table are equal if and only if their
+/// strings are, and they count up from zero, so they can index a table directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    fn at(index: usize) -> Self {
+        Self(u32::try_from(index).expect("Too many strings have been interned."))
+    }
+}
+
+pub fn intern(string: String, vm: &mut Vm) -> Symbol {
+    vm.strings.intern(string)
+}
+
+/// Strings that won't change anymore, e.g., the names of functions and the strings of a
+/// compiled template. Each one is stored once and identified by its position.
+#[derive(Clone, Default)]
+pub struct Interner {
+    strings: Vec<Arc<str>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+}
+
+impl Interner {
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(symbol) = self.symbols.get(string) {
+            return *symbol;
+        }
+
+        let symbol = Symbol::at(self.strings.len());
+        let string: Arc<str> = Arc::from(string);
+        self.strings.push(Arc::clone(&string));
+        self.symbols.insert(string, symbol);
+
+        symbol
+    }
+
+    pub fn lookup(&self, string: &str) -> Option<Symbol> {
+        self.symbols.get(string).copied()
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index()).map(|string| &**string)
+    }
+
+    fn len(&self) -> usize {
+        self.strings.len()
+    }
 }
 
-pub fn intern(string: String, vm: &mut Vm) -> u64 {
-    let hash = hash_of(&string);
+/// Interned strings in two layers: `base` holds the strings of a compiled template and is
+/// shared by every execution of it, while `scratch` holds strings interned by one execution.
+/// Scratch symbols continue where the base's leave off, and the slots of collected strings
+/// are reused.
+#[derive(Default)]
+pub struct StringTable {
+    base: Arc<Interner>,
+    scratch: Vec<Option<Arc<str>>>,
+    symbols: HashMap<Arc<str>, Symbol>,
+    free: Vec<Symbol>,
+}
+
+impl StringTable {
+    pub fn new(base: Arc<Interner>) -> Self {
+        Self {
+            base,
+            scratch: Vec::new(),
+            symbols: HashMap::new(),
+            free: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, symbol: Symbol) -> Option<&str> {
+        match symbol.index().checked_sub(self.base.len()) {
+            None => self.base.get(symbol),
+            Some(index) => self.scratch.get(index)?.as_deref(),
+        }
+    }
+
+    /// The symbol of `string` if it has been interned. Unlike `intern`, nothing is added.
+    pub fn lookup(&self, string: &str) -> Option<Symbol> {
+        self.base
+            .lookup(string)
+            .or_else(|| self.symbols.get(string).copied())
+    }
+
+    pub fn intern(&mut self, string: String) -> Symbol {
+        if let Some(symbol) = self.lookup(&string) {
+            return symbol;
+        }
+
+        let string: Arc<str> = Arc::from(string);
+        let symbol = if let Some(symbol) = self.free.pop() {
+            self.scratch[symbol.index() - self.base.len()] = Some(Arc::clone(&string));
+            symbol
+        } else {
+            self.scratch.push(Some(Arc::clone(&string)));
+            Symbol::at(self.base.len() + self.scratch.len() - 1)
+        };
+        self.symbols.insert(string, symbol);
+
+        symbol
+    }
+
+    /// The number of strings interned by the current execution.
+    pub fn scratch_len(&self) -> usize {
+        self.symbols.len()
+    }
 
-    vm.strings.borrow_mut().entry(hash).or_insert(string);
+    /// Drops the strings interned by the current execution that aren't in `marked`.
+    pub fn retain(&mut self, marked: &HashSet<Symbol>) {
+        let base_len = self.base.len();
+
+        for (index, slot) in self.scratch.iter_mut().enumerate() {
+            let symbol = Symbol::at(base_len + index);
+            if marked.contains(&symbol) {
+                continue;
+            }
+            if let Some(string) = slot.take() {
+                self.symbols.remove(&string);
+                self.free.push(symbol);
+            }
+        }
+        self.symbols.shrink_to(self.symbols.len() * 2);
+    }
 
-    hash
+    pub fn clear(&mut self) {
+        self.base = Arc::default();
+        self.scratch = Vec::new();
+        self.symbols = HashMap::new();
+        self.free = Vec::new();
+    }
+
+    /// Merges `scratch` into a new base that can be shared across threads. Symbols keep
+    /// their IDs.
+    pub fn freeze(self) -> Arc<Interner> {
+        let mut base = Arc::try_unwrap(self.base).unwrap_or_else(|base| (*base).clone());
+        for slot in self.scratch {
+            // a collected slot is kept empty so that the symbols after it stay the same
+            let string = slot.unwrap_or_else(|| Arc::from(""));
+            let symbol = Symbol::at(base.strings.len());
+            base.strings.push(Arc::clone(&string));
+            base.symbols.entry(string).or_insert(symbol);
+        }
+
+        Arc::new(base)
+    }
+}
+
+impl Index<Symbol> for StringTable {
+    type Output = str;
+
+    fn index(&self, symbol: Symbol) -> &str {
+        self.get(symbol).expect("String should have been interned.")
+    }
 }
@@ -0,0 +1,89 @@
+use crate::host::HostValue;
+use crate::object::ObjType;
+use crate::value::{Value, ValueType};
+use crate::vm::Vm;
+
+use std::{fmt, time::Duration};
+
+/// Hooks that are notified as a render runs, e.g., for audit logs, metrics, or tracing.
+/// Every method does nothing unless overridden.
+///
+/// An observer is attached to a render through its `Context`, and it's called on the thread
+/// doing the rendering, so slow hooks slow the render down.
+///
+/// ```ignore
+/// struct Timings;
+///
+/// impl Observer for Timings {
+///     fn on_call(&self, call: &Call) {
+///         eprintln!("{}() took {:?}", call.name, call.duration);
+///     }
+/// }
+///
+/// let context = Context::new().observer(Arc::new(Timings));
+/// ```
+pub trait Observer: Send + Sync {
+    /// Called after each function, built-in or host-defined, returns.
+    fn on_call(&self, _call: &Call) {}
+
+    /// Called before each SQL statement a data extension function runs.
+    fn on_query(&self, _sql: &str) {}
+
+    /// Called with each chunk of output before it's written.
+    fn on_output(&self, _chunk: &str) {}
+}
+
+/// A finished function call. Its arguments and result are only converted to `HostValue`s when
+/// asked for, so that observers that don't look at them cost nothing extra.
+pub struct Call<'a> {
+    /// The function's name in lowercase.
+    pub name: &'a str,
+    pub duration: Duration,
+    pub(crate) args: &'a [Value],
+    pub(crate) result: &'a Result<Value, Value>,
+    pub(crate) vm: &'a Vm,
+}
+
+impl Call<'_> {
+    pub fn arg_count(&self) -> usize {
+        self.args.len()
+    }
+
+    pub fn arg(&self, index: usize) -> Option<HostValue> {
+        let val = self.args.get(index)?;
+
+        Some(HostValue::from_value(val, self.vm))
+    }
+
+    pub fn args(&self) -> Vec<HostValue> {
+        self.args
+            .iter()
+            .map(|val| HostValue::from_value(val, self.vm))
+            .collect()
+    }
+
+    /// The returned value or the error's message.
+    pub fn result(&self) -> Result<HostValue, &str> {
+        match self.result {
+            Ok(val) => Ok(HostValue::from_value(val, self.vm)),
+            Err(val) => match &val.type_ {
+                ValueType::Obj(obj) => match &obj.type_ {
+                    ObjType::Error(error_obj) => Err(error_obj.message.as_str()),
+                    _ => Err(""),
+                },
+                _ => Err(""),
+            },
+        }
+    }
+}
+
+impl fmt::Debug for Call<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Call")
+            .field("name", &self.name)
+            .field("args", &self.args())
+            .field("result", &self.result())
+            .field("duration", &self.duration)
+            .finish()
+    }
+}
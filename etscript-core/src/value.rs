@@ -1,8 +1,16 @@
-use crate::object::{NativeFn, NativeFnObj, Obj, ObjType, RowObj, RowsetObj, StringObj};
+use crate::functions::datetime;
+use crate::host::HostFn;
+use crate::object::{
+    ErrorObj, HostFnObj, NativeFn, NativeFnObj, Obj, ObjType, RowObj, RowsetObj, StringObj,
+    StringTable, Symbol,
+};
 use crate::vm::Vm;
+use crate::ErrorKind;
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
+use std::sync::Arc;
 
 #[derive(Clone)]
 pub enum ValueType {
@@ -50,9 +58,15 @@ impl Value {
         }
     }
 
-    pub fn nfn(hash: u64, nfn: NativeFn) -> Self {
+    pub fn nfn(symbol: Symbol, nfn: NativeFn) -> Self {
         Self::from(Obj {
-            type_: ObjType::NativeFn(NativeFnObj::new(hash, nfn)),
+            type_: ObjType::NativeFn(NativeFnObj::new(symbol, nfn)),
+        })
+    }
+
+    pub fn hfn(symbol: Symbol, arity: (u8, u8), hfn: Arc<HostFn>) -> Self {
+        Self::from(Obj {
+            type_: ObjType::HostFn(HostFnObj::new(symbol, arity, hfn)),
         })
     }
 
@@ -73,21 +87,26 @@ impl Value {
         })
     }
 
-    pub fn row(map: HashMap<u64, Self>) -> Self {
+    pub fn row(map: HashMap<Symbol, Self>) -> Self {
         Self::from(Obj {
-            type_: ObjType::Row(RowObj::new(map)),
+            type_: ObjType::Row(Arc::new(RowObj::new(map))),
         })
     }
 
     pub fn rowset(vec: Vec<Self>) -> Self {
         Self::from(Obj {
-            type_: ObjType::Rowset(RowsetObj::new(vec)),
+            type_: ObjType::Rowset(Arc::new(RowsetObj::new(vec))),
         })
     }
 
+    /// An error raised by a function for a reason that has no more specific `ErrorKind`.
     pub fn error(string: String) -> Self {
+        Self::error_of(ErrorKind::Function, string)
+    }
+
+    pub fn error_of(kind: ErrorKind, string: String) -> Self {
         Self::from(Obj {
-            type_: ObjType::Error(string),
+            type_: ObjType::Error(Arc::new(ErrorObj::new(kind, string))),
         })
     }
 
@@ -115,7 +134,7 @@ impl Value {
             if obj.is_string() {
                 let string = self.to_string(vm);
                 let Ok(_) = string.parse::<f64>() else {
-                    return (false, None)
+                    return (false, None);
                 };
                 return (true, Some(string));
             }
@@ -165,6 +184,13 @@ impl Value {
         false
     }
 
+    pub fn is_hfn(&self) -> bool {
+        if let ValueType::Obj(obj) = &self.type_ {
+            return obj.is_hfn();
+        }
+        false
+    }
+
     pub fn is_string(&self) -> bool {
         if let ValueType::Obj(obj) = &self.type_ {
             return obj.is_string();
@@ -243,66 +269,68 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
 
     pub fn to_string(&self, vm: &Vm) -> String {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
         obj.get(strings).to_owned()
     }
 
-    pub fn to_string_hash(&self, vm: &Vm) -> (String, u64) {
+    pub fn to_string_symbol(&self, vm: &Vm) -> (String, Symbol) {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
-        (obj.get(strings).to_owned(), obj.hash)
+        (obj.get(strings).to_owned(), obj.symbol)
     }
 
     pub fn to_lower(&self, vm: &Vm) -> String {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
         obj.get(strings).to_lowercase()
     }
 
     pub fn to_ascii_lower(&self, vm: &Vm) -> String {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
         obj.get(strings).to_ascii_lowercase()
     }
 
     pub fn to_upper(&self, vm: &Vm) -> String {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
         obj.get(strings).to_uppercase()
     }
 
     pub fn to_ascii_upper(&self, vm: &Vm) -> String {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
 
         obj.get(strings).to_ascii_uppercase()
     }
 
     pub fn to_c_string(&self, vm: &Vm) -> Result<CString, Value> {
         let obj: StringObj = self.clone().into();
-        let strings = &vm.strings.borrow();
+        let strings = &vm.strings;
         let string = obj.get(strings).as_bytes();
 
         let Ok(c_string) = CString::new(string) else {
-            return Err(Value::error(r"Interior nul byte (`\0`) was found.".to_owned()));
+            return Err(Value::error(
+                r"Interior nul byte (`\0`) was found.".to_owned(),
+            ));
         };
 
         Ok(c_string)
     }
 
-    pub fn to_row(&self) -> RowObj {
-        let row: RowObj = self.clone().into();
+    pub fn to_row(&self) -> Arc<RowObj> {
+        let row: Arc<RowObj> = self.clone().into();
 
         row
     }
 
-    pub fn to_rowset(&self) -> RowsetObj {
-        let rowset: RowsetObj = self.clone().into();
+    pub fn to_rowset(&self) -> Arc<RowsetObj> {
+        let rowset: Arc<RowsetObj> = self.clone().into();
 
         rowset
     }
@@ -319,7 +347,7 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
         if let ValueType::Obj(obj) = &self.type_ {
             match obj.type_ {
                 ObjType::String(string_obj) => {
-                    let string = string_obj.get(&vm.strings.borrow()).to_lowercase();
+                    let string = string_obj.get(&vm.strings).to_lowercase();
                     matches!(string.as_str(), truthy_pattern!())
                 }
                 _ => false,
@@ -329,7 +357,10 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
         }
     }
 
-    pub fn are_vals_eq(a: &Self, b: &Self, strings: &HashMap<u64, String>) -> bool {
+    /// AMPscript's `==`, which ignores the case of strings unless `vm` is strict.
+    pub fn are_vals_eq(a: &Self, b: &Self, vm: &Vm) -> bool {
+        let strings = &vm.strings;
+
         match &a.type_ {
             ValueType::Null(_) => b.is_null(),
             ValueType::Number(a) => match &b.type_ {
@@ -342,7 +373,7 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                 ValueType::Obj(obj) => match obj.type_ {
                     ObjType::String(string_obj) => {
                         let string = string_obj.get(strings);
-                        if let Ok(num) = string.parse::<f64>() {
+                        if let Some(num) = parse_num(string) {
                             *a == num
                         } else {
                             false
@@ -359,6 +390,7 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                 },
                 ValueType::Boolean(boolean) => *a == *boolean,
                 ValueType::Obj(obj) => match obj.type_ {
+                    ObjType::String(string_obj) if string_obj.is_datetime => false,
                     ObjType::String(string_obj) => {
                         let string = string_obj.get(strings).to_lowercase();
                         match string.as_str() {
@@ -374,12 +406,13 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                     ValueType::Null(_) => false,
                     ValueType::Number(num_b) => {
                         let string = obj_a.get(strings);
-                        if let Ok(num_a) = string.parse::<f64>() {
+                        if let Some(num_a) = parse_num(string) {
                             num_a == *num_b
                         } else {
                             false
                         }
                     }
+                    ValueType::Boolean(_) if obj_a.is_datetime => false,
                     ValueType::Boolean(boolean) => {
                         let string = obj_a.get(strings).to_lowercase();
                         match string.as_str() {
@@ -389,10 +422,23 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                     }
                     ValueType::Obj(b) => match b.type_ {
                         ObjType::String(obj_b) => {
-                            if obj_a.is_datetime && obj_b.is_datetime {
-                                obj_a.data == obj_b.data
+                            if obj_a.is_datetime || obj_b.is_datetime {
+                                // a string is parsed as a date, and never equals one otherwise
+                                let millis_a = unix_millis(&obj_a, vm);
+                                millis_a.is_some() && millis_a == unix_millis(&obj_b, vm)
+                            } else if obj_a.symbol == obj_b.symbol {
+                                // equal strings always share a symbol
+                                true
+                            } else if let (Some(num_a), Some(num_b)) =
+                                (parse_num(obj_a.get(strings)), parse_num(obj_b.get(strings)))
+                            {
+                                num_a == num_b
+                            } else if vm.is_strict {
+                                false
                             } else {
-                                obj_a.get(strings) == obj_b.get(strings)
+                                let a = obj_a.get(strings).chars().flat_map(char::to_lowercase);
+                                let b = obj_b.get(strings).chars().flat_map(char::to_lowercase);
+                                a.eq(b)
                             }
                         }
                         _ => false,
@@ -403,7 +449,78 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
         }
     }
 
-    pub fn print(&self, strings: &HashMap<u64, String>) {
+    /// How `a` orders against `b` for `<`, `<=`, `>`, and `>=`, following the coercion table in
+    /// the README. `None` means unordered, as when comparing with NaN, which makes each of those
+    /// operators false. Operands that can't be compared, e.g., a number and a non-numeric string,
+    /// are an error.
+    pub fn compare(a: &Self, b: &Self, vm: &Vm) -> Result<Option<Ordering>, String> {
+        if let (ValueType::Number(num_a), ValueType::Number(num_b)) = (&a.type_, &b.type_) {
+            return Ok(num_a.partial_cmp(num_b));
+        }
+
+        if (a.is_obj() && !a.is_string()) || (b.is_obj() && !b.is_string()) {
+            return Err("Rows, rowsets, and errors can't be compared.".to_owned());
+        }
+        // null is less than everything else
+        if a.is_null() || b.is_null() {
+            return Ok(Some(b.is_null().cmp(&a.is_null())));
+        }
+
+        if a.is_datetime() || b.is_datetime() {
+            let to_millis = |val: &Self| {
+                if val.is_string() {
+                    unix_millis(&val.clone().into(), vm)
+                } else {
+                    None
+                }
+            };
+            let (Some(millis_a), Some(millis_b)) = (to_millis(a), to_millis(b)) else {
+                return Err(
+                    "A date can only be compared with another date or a string that parses as one."
+                        .to_owned(),
+                );
+            };
+            return Ok(Some(millis_a.cmp(&millis_b)));
+        }
+
+        // false is less than true
+        if a.is_bool() || b.is_bool() {
+            return Ok(Some(a.is_truthy(vm).cmp(&b.is_truthy(vm))));
+        }
+
+        match (a.as_num(vm), b.as_num(vm)) {
+            (Some(num_a), Some(num_b)) => return Ok(num_a.partial_cmp(&num_b)),
+            _ if a.is_num() || b.is_num() => {
+                return Err(
+                    "A number can only be compared with another number or a numeric string."
+                        .to_owned(),
+                );
+            }
+            _ => (),
+        }
+
+        let string_a = a.to_string(vm);
+        let string_b = b.to_string(vm);
+
+        if vm.is_strict {
+            Ok(Some(string_a.cmp(&string_b)))
+        } else {
+            let chars_a = string_a.chars().flat_map(char::to_lowercase);
+            let chars_b = string_b.chars().flat_map(char::to_lowercase);
+            Ok(Some(chars_a.cmp(chars_b)))
+        }
+    }
+
+    // a number, or a string that parses as one
+    fn as_num(&self, vm: &Vm) -> Option<f64> {
+        match &self.type_ {
+            ValueType::Number(num) => Some(*num),
+            ValueType::Obj(_) if self.is_string() => parse_num(&self.to_string(vm)),
+            _ => None,
+        }
+    }
+
+    pub fn print(&self, strings: &StringTable) {
         match &self.type_ {
             ValueType::Null(_) => {
                 print!("<null>");
@@ -415,7 +532,7 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                 print!("{boolean}");
             }
             ValueType::Obj(obj) => match &obj.type_ {
-                ObjType::NativeFn(_) => {
+                ObjType::NativeFn(_) | ObjType::HostFn(_) => {
                     print!("<fn>");
                 }
                 ObjType::String(string_obj) => {
@@ -427,12 +544,36 @@ or falsey (`0`, `false`, `f`, `no`, `n`)."
                 ObjType::Rowset(_) => {
                     print!("<rowset>");
                 }
-                ObjType::Error(string) => {
-                    print!("Error: {string}");
+                ObjType::Error(error_obj) => {
+                    print!("Error: {}", error_obj.message);
                 }
             },
         }
     }
+
+    /// Adds the symbol of every string this value refers to, including the field names and
+    /// values of rows.
+    pub fn mark_strings(&self, marked: &mut HashSet<Symbol>) {
+        if let ValueType::Obj(obj) = &self.type_ {
+            match &obj.type_ {
+                ObjType::String(string_obj) => {
+                    marked.insert(string_obj.symbol);
+                }
+                ObjType::Row(row_obj) => {
+                    for (symbol, val) in &row_obj.map {
+                        marked.insert(*symbol);
+                        val.mark_strings(marked);
+                    }
+                }
+                ObjType::Rowset(rowset_obj) => {
+                    for val in &rowset_obj.vec {
+                        val.mark_strings(marked);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
 }
 
 //
@@ -497,6 +638,15 @@ impl From<Value> for NativeFnObj {
     }
 }
 
+impl From<Value> for HostFnObj {
+    fn from(val: Value) -> Self {
+        let ValueType::Obj(obj) = val.type_ else {
+            panic!("Incorrect variant of `ValueType` was used. Expected `ValueType::Obj`.");
+        };
+        obj.into()
+    }
+}
+
 impl From<Value> for StringObj {
     fn from(val: Value) -> Self {
         let ValueType::Obj(obj) = val.type_ else {
@@ -506,7 +656,7 @@ impl From<Value> for StringObj {
     }
 }
 
-impl From<Value> for RowObj {
+impl From<Value> for Arc<RowObj> {
     fn from(val: Value) -> Self {
         let ValueType::Obj(obj) = val.type_ else {
             panic!("Incorrect variant of `ValueType` was used. Expected `ValueType::Obj`.");
@@ -515,7 +665,7 @@ impl From<Value> for RowObj {
     }
 }
 
-impl From<Value> for RowsetObj {
+impl From<Value> for Arc<RowsetObj> {
     fn from(val: Value) -> Self {
         let ValueType::Obj(obj) = val.type_ else {
             panic!("Incorrect variant of `ValueType` was used. Expected `ValueType::Obj`.");
@@ -523,3 +673,32 @@ impl From<Value> for RowsetObj {
         obj.into()
     }
 }
+
+// a numeric string's number, e.g., "-2.5". Only plain decimals count, so "05", "+5", "1e3",
+// "inf", and "NaN" compare as text.
+fn parse_num(string: &str) -> Option<f64> {
+    fn is_digits(digits: &str) -> bool {
+        !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())
+    }
+
+    let unsigned = string.strip_prefix('-').unwrap_or(string);
+    let (whole, fraction) = match unsigned.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (unsigned, None),
+    };
+    let has_leading_zero = whole.len() > 1 && whole.starts_with('0');
+    if !is_digits(whole) || has_leading_zero || !fraction.map_or(true, is_digits) {
+        return None;
+    }
+
+    string.parse().ok()
+}
+
+// a date's instant, or that of a string that parses as one
+fn unix_millis(obj: &StringObj, vm: &Vm) -> Option<i64> {
+    if obj.is_datetime {
+        Some(obj.data)
+    } else {
+        datetime::parse_unix_millis(obj.get(&vm.strings))
+    }
+}
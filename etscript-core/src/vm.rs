@@ -1,59 +1,178 @@
 use crate::bytecode::{Bytecode, Opcode};
 use crate::compiler::Compiler;
+use crate::db::Database;
 use crate::debug::{self, STYLE_DIM, STYLE_RESET, STYLE_YELLOW};
-use crate::functions;
-use crate::object::{NativeFnObj, ObjType, StringObj};
+use crate::diagnostic;
+use crate::host::{self, HostValue};
+use crate::object::{self, HostFnObj, NativeFnObj, ObjType, StringObj, StringTable, Symbol};
+use crate::observer::{Call, Observer};
 use crate::value::{Value, ValueType};
+use crate::{CancelToken, Clock, Diagnostic, ErrorKind, Limits, Progress, Severity, Whitespace};
+
+use rand::{rngs::StdRng, SeedableRng};
 
 use std::{
-    collections::HashMap,
-    {cell::RefCell, rc::Rc},
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{self, Write},
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+/// The outcome of compiling or rendering. The values are stable, and C callers receive them
+/// as `EtStatus`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Status {
-    Ok,
-    InputError,
-    DatabaseError,
-    CompileError,
-    RuntimeError,
+    Ok = 0,
+    /// The source could not be read (e.g., it is not valid UTF-8).
+    InputError = 1,
+    /// The database could not be opened or initialized.
+    DatabaseError = 2,
+    CompileError = 3,
+    RuntimeError = 4,
+    /// Output could not be written to the sink passed to `render_to`.
+    OutputError = 5,
+    LimitExceeded = 6,
+    Cancelled = 7,
 }
 
 pub struct Vm {
     pub stack: Vec<Value>,
     current_offset: usize,
-    pub bc: Bytecode,
-    pub strings: Rc<RefCell<HashMap<u64, String>>>,
-    globals: HashMap<u64, Value>,
-    pub funcs: HashMap<u64, Value>,
-    pub result: Rc<RefCell<String>>,
+    pub bc: Arc<Bytecode>,
+    pub strings: StringTable,
+    /// Global variables by the slot the compiler assigned them. `None` until defined.
+    globals: Vec<Option<Value>>,
+    /// The name of each global's slot: the program's, then any others the host defined.
+    global_names: Vec<Symbol>,
+    /// The slots of globals defined by the host, which `var` leaves as they are.
+    host_globals: HashSet<usize>,
+    pub attrs: HashMap<Symbol, Value>,
+    pub funcs: Arc<HashMap<Symbol, Value>>,
+    /// The message of the error that stopped compilation or execution.
+    pub error: String,
+    /// What kind of error stopped compilation or execution, if more is known than its `Status`.
+    pub error_kind: Option<ErrorKind>,
+    /// The function whose error stopped execution.
+    pub error_function: Option<String>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub database: Database,
+    pub limits: Limits,
+    pub cancel_token: Option<CancelToken>,
+    pub clock: Clock,
+    /// The source of `Random()` and `Guid()`.
+    pub rng: StdRng,
+    pub observer: Option<Arc<dyn Observer>>,
+    /// Whether variable names, field names, and string equality are case-sensitive.
+    pub is_strict: bool,
+    /// How the compiler treats whitespace around blocks.
+    pub whitespace: Whitespace,
+    usage: Usage,
+}
+
+/// Resources used by the current execution, counted against `Vm::limits`.
+#[derive(Default)]
+struct Usage {
+    instructions: u64,
+    output_bytes: u64,
+    de_calls: u32,
+    started: Option<Instant>,
+    /// Set when a limit or cancellation stops execution from within a function.
+    halt: Option<Status>,
+    /// Unreachable strings are collected once this many have been interned.
+    next_collection: usize,
 }
 
 impl Vm {
     const STACK_MAX: usize = (u8::MAX as usize + 1) * 64;
+    // the wall clock and cancellation token are read once per this many instructions
+    const CHECK_INTERVAL: u64 = 1024;
+    const COLLECTION_MIN: usize = 4096;
 
-    pub fn new(strings: Rc<RefCell<HashMap<u64, String>>>, result: Rc<RefCell<String>>) -> Self {
+    pub fn new(strings: StringTable, funcs: Arc<HashMap<Symbol, Value>>) -> Self {
         Self {
             stack: Vec::with_capacity(Self::STACK_MAX),
             current_offset: 0,
-            bc: Bytecode::default(),
+            bc: Arc::default(),
             strings,
-            globals: HashMap::new(),
-            funcs: HashMap::new(),
-            result,
+            globals: Vec::new(),
+            global_names: Vec::new(),
+            host_globals: HashSet::new(),
+            attrs: HashMap::new(),
+            funcs,
+            error: String::new(),
+            error_kind: None,
+            error_function: None,
+            diagnostics: Vec::new(),
+            database: Database::default(),
+            limits: Limits::default(),
+            cancel_token: None,
+            clock: Clock::System,
+            rng: StdRng::from_rng(rand::thread_rng()).expect("Thread RNG should have been seeded."),
+            observer: None,
+            is_strict: false,
+            whitespace: Whitespace::Preserve,
+            usage: Usage::default(),
         }
     }
 
-    pub fn reset(&mut self) {
-        self.stack.clear();
-        self.current_offset = 0;
-        self.bc.clear();
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// The name that a variable or field named `name` is stored under: lowercase unless strict.
+    pub fn fold_name(&self, name: &str) -> String {
+        if self.is_strict {
+            name.to_owned()
+        } else {
+            name.to_ascii_lowercase()
+        }
+    }
+
+    /// Attribute names are case-insensitive, e.g., `%%FirstName%%` and `%%[firstname]%%`
+    /// both resolve to an attribute defined as `FIRSTNAME`.
+    pub fn define_attr(&mut self, name: &str, value: &str) {
+        let symbol = object::intern(name.to_ascii_lowercase(), self);
+        let val = Value::string(value.to_owned(), self);
 
-        self.strings.borrow_mut().clear();
-        self.strings.borrow_mut().shrink_to_fit();
-        self.globals.clear();
-        self.globals.shrink_to_fit();
-        self.funcs.clear();
-        self.funcs.shrink_to_fit();
+        self.attrs.insert(symbol, val);
+    }
+
+    /// Defines a variable before the program runs, e.g., `@segment`. The `@` is optional.
+    /// Must be called after the program is loaded.
+    pub fn define_global(&mut self, name: &str, value: HostValue) {
+        let mut name = self.fold_name(name);
+        if !name.starts_with('@') {
+            name.insert(0, '@');
+        }
+        let symbol = object::intern(name, self);
+        let val = value.into_value(self);
+
+        // a variable the program never mentions still gets a slot so it can be exported
+        let slot = if let Some(slot) = self.global_names.iter().position(|n| *n == symbol) {
+            slot
+        } else {
+            self.global_names.push(symbol);
+            self.globals.push(None);
+            self.global_names.len() - 1
+        };
+
+        self.globals[slot] = Some(val);
+        self.host_globals.insert(slot);
+    }
+
+    /// Every variable and its value, keyed by its name, e.g., `@segment`. Names are lowercase
+    /// unless strict.
+    pub fn export_globals(&self) -> BTreeMap<String, HostValue> {
+        self.global_names
+            .iter()
+            .zip(&self.globals)
+            .filter_map(|(symbol, val)| {
+                let name = self.strings.get(*symbol)?.to_owned();
+                Some((name, HostValue::from_value(val.as_ref()?, self)))
+            })
+            .collect()
     }
 
     //
@@ -89,46 +208,278 @@ impl Vm {
             | self.bc.byte_at(self.current_offset - 1) as u16
     }
 
+    /// Reads the operand of an instruction that has a one-byte and a two-byte form.
+    fn read_index(&mut self, is_short: bool) -> usize {
+        if is_short {
+            self.read_bytes() as usize
+        } else {
+            self.read_byte() as usize
+        }
+    }
+
     fn read_const(&self, offset: usize) -> Value {
         self.bc.const_at(offset).clone()
     }
 
-    fn write(&mut self, val: &Value) {
+    fn write(&mut self, val: &Value, out: &mut dyn Write) -> Result<(), Status> {
         match &val.type_ {
-            ValueType::Null(_) => {
-                *self.result.borrow_mut() += "";
-            }
-            ValueType::Number(num) => {
-                *self.result.borrow_mut() += &format!("{num}");
-            }
-            ValueType::Boolean(boolean) => {
-                *self.result.borrow_mut() += &format!("{boolean}");
-            }
+            ValueType::Null(_) => Ok(()),
+            ValueType::Number(num) => self.emit(&format!("{num}"), out),
+            ValueType::Boolean(boolean) => self.emit(&format!("{boolean}"), out),
             ValueType::Obj(obj) => match &obj.type_ {
-                ObjType::NativeFn(_) => {
-                    *self.result.borrow_mut() += "";
-                }
+                ObjType::NativeFn(_) | ObjType::HostFn(_) => Ok(()),
                 ObjType::String(string_obj) => {
-                    *self.result.borrow_mut() += string_obj.get(&self.strings.borrow());
-                }
-                ObjType::Row(_) => {
-                    *self.result.borrow_mut() += "";
-                }
-                ObjType::Rowset(_) => {
-                    *self.result.borrow_mut() += "";
+                    let len = string_obj.get(&self.strings).len();
+                    if len == 0 {
+                        return Ok(());
+                    }
+                    self.count_output(len)?;
+                    let string = string_obj.get(&self.strings);
+                    self.observe_output(string);
+
+                    out.write_all(string.as_bytes())
+                        .map_err(|err| self.output_error(err))
                 }
-                ObjType::Error(string) => {
-                    self.result.borrow_mut().clear();
-                    *self.result.borrow_mut() += "Error: ";
-                    *self.result.borrow_mut() += string;
+                ObjType::Row(_) => Ok(()),
+                ObjType::Rowset(_) => Ok(()),
+                ObjType::Error(error_obj) => {
+                    self.error = error_obj.message.clone();
+                    self.error_kind = Some(error_obj.kind);
+                    Err(Status::RuntimeError)
                 }
             },
         }
     }
 
+    fn emit(&mut self, chunk: &str, out: &mut dyn Write) -> Result<(), Status> {
+        // observers shouldn't hear about output that didn't happen
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.count_output(chunk.len())?;
+        self.observe_output(chunk);
+
+        out.write_all(chunk.as_bytes())
+            .map_err(|err| self.output_error(err))
+    }
+
+    fn output_error(&mut self, err: io::Error) -> Status {
+        self.error = format!("Output could not be written: {err}");
+
+        Status::OutputError
+    }
+
+    //
+
+    fn observe_output(&self, chunk: &str) {
+        if let Some(observer) = &self.observer {
+            observer.on_output(chunk);
+        }
+    }
+
+    pub fn observe_query(&self, sql: &str) {
+        if let Some(observer) = &self.observer {
+            observer.on_query(sql);
+        }
+    }
+
+    fn observe_call(
+        &self,
+        symbol: Symbol,
+        args: &[Value],
+        result: &Result<Value, Value>,
+        duration: Duration,
+    ) {
+        if let Some(observer) = &self.observer {
+            observer.on_call(&Call {
+                name: self.strings.get(symbol).unwrap_or(""),
+                duration,
+                args,
+                result,
+                vm: self,
+            });
+        }
+    }
+
+    fn undefined_global(&mut self, slot: usize) -> Status {
+        let name = &self.strings[self.global_names[slot]];
+        self.error = format!("Undefined variable: `{name}`.");
+        self.error_kind = Some(ErrorKind::UndefinedVariable);
+
+        Status::RuntimeError
+    }
+
+    fn exceed(&mut self, err_msg: String) -> Status {
+        self.error = err_msg;
+        self.usage.halt = Some(Status::LimitExceeded);
+
+        Status::LimitExceeded
+    }
+
+    fn check_cancelled(&mut self) -> Result<(), Status> {
+        if self
+            .cancel_token
+            .as_ref()
+            .is_some_and(CancelToken::is_cancelled)
+        {
+            self.error = "Render was cancelled.".to_owned();
+            self.usage.halt = Some(Status::Cancelled);
+
+            return Err(Status::Cancelled);
+        }
+
+        Ok(())
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            instructions: self.usage.instructions,
+            output_bytes: self.usage.output_bytes,
+            data_extension_calls: self.usage.de_calls,
+            line: self.bc.line_num_at(self.current_offset.saturating_sub(1)) as u32,
+        }
+    }
+
+    fn count_instruction(&mut self) -> Result<(), Status> {
+        self.usage.instructions += 1;
+
+        if let Some(max) = self.limits.instructions {
+            if self.usage.instructions > max {
+                return Err(self.exceed(format!("Instruction limit of {max} was exceeded.")));
+            }
+        }
+        if self.usage.instructions % Self::CHECK_INTERVAL == 0 {
+            self.check_cancelled()?;
+            self.check_time()?;
+
+            if self.strings.scratch_len() > self.usage.next_collection {
+                self.collect_strings();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Drops the strings interned by this execution that no longer can be reached, e.g., the
+    /// intermediate results of concatenating in a loop. Functions only intern strings while
+    /// they're being called, so anything reachable is on the stack or in a global or attribute.
+    fn collect_strings(&mut self) {
+        let mut marked = HashSet::with_capacity(self.strings.scratch_len());
+
+        // names of attributes and of variables defined by the host are interned at runtime
+        marked.extend(self.global_names.iter().chain(self.attrs.keys()));
+        for val in self
+            .stack
+            .iter()
+            .chain(self.globals.iter().flatten())
+            .chain(self.attrs.values())
+        {
+            val.mark_strings(&mut marked);
+        }
+
+        self.strings.retain(&marked);
+        self.usage.next_collection = (self.strings.scratch_len() * 2).max(Self::COLLECTION_MIN);
+    }
+
+    /// Only counts output that's within the limit, so `Progress` never includes a rejected chunk.
+    fn count_output(&mut self, len: usize) -> Result<(), Status> {
+        let output_bytes = self.usage.output_bytes + len as u64;
+
+        if let Some(max) = self.limits.output_bytes {
+            if output_bytes > max {
+                return Err(self.exceed(format!("Output limit of {max} bytes was exceeded.")));
+            }
+        }
+        self.usage.output_bytes = output_bytes;
+
+        Ok(())
+    }
+
+    fn check_time(&mut self) -> Result<(), Status> {
+        let (Some(max), Some(started)) = (self.limits.wall_time, self.usage.started) else {
+            return Ok(());
+        };
+        if started.elapsed() > max {
+            return Err(self.exceed(format!(
+                "Time limit of {} ms was exceeded.",
+                max.as_millis()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Called by data extension functions before they open the database.
+    pub fn count_de_call(&mut self) -> Result<(), Value> {
+        let status = match self.limits.data_extension_calls {
+            Some(max) if self.usage.de_calls >= max => {
+                Err(self.exceed(format!("Data extension call limit of {max} was exceeded.")))
+            }
+            _ => self.check_cancelled().and_then(|_| self.check_time()),
+        };
+        // a call that's refused was never made
+        if status.is_ok() {
+            self.usage.de_calls += 1;
+        }
+
+        // the function's own error is replaced by `self.usage.halt` in `dispatch()`
+        status.map_err(|_| Value::error(self.error.clone()))
+    }
+
     //
 
-    pub fn run(&mut self, source: &'static str) -> Result<(), Status> {
+    pub fn compile(&mut self, source: &str) -> Result<Bytecode, Status> {
+        Compiler::new(&mut *self, source).compile()
+    }
+
+    /// Prepares to run `bc`, giving each of its globals an empty slot. Strings interned during
+    /// compilation must already be present in `self.strings`.
+    pub fn load(&mut self, bc: Arc<Bytecode>) {
+        self.globals = vec![None; bc.globals().len()];
+        self.global_names = bc.globals().to_vec();
+        self.host_globals.clear();
+        self.bc = bc;
+    }
+
+    /// Runs the loaded bytecode, which was compiled from `source`, writing output to `out` as
+    /// it goes.
+    ///
+    /// Errors are reported through `self.error` rather than `out`, so whatever was written
+    /// before an error stays written.
+    pub fn execute(&mut self, source: &str, out: &mut dyn Write) -> Result<(), Status> {
+        self.stack.clear();
+        self.current_offset = 0;
+        self.usage = Usage {
+            started: Some(Instant::now()),
+            next_collection: Self::COLLECTION_MIN,
+            ..Usage::default()
+        };
+
+        let status = self
+            .check_cancelled()
+            .and_then(|_| self.dispatch(source, out));
+
+        if status.is_err() {
+            // operands share the position of their instruction
+            let offset = self
+                .bc
+                .source_offset_at(self.current_offset.saturating_sub(1));
+            let (line, column) = diagnostic::position_at(source, offset);
+            let message = self.error.clone();
+
+            self.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                line,
+                column,
+                span: None,
+                message,
+            });
+        }
+
+        status
+    }
+
+    fn dispatch(&mut self, source: &str, out: &mut dyn Write) -> Result<(), Status> {
         macro_rules! binary_op {
             ($op:tt) => {
                 let mut do_compare = true;
@@ -156,18 +507,33 @@ impl Vm {
                 }
 
                 if !do_compare {
-                    *self.result.borrow_mut() = "Operands must be numbers.".to_owned();
+                    self.error = "Operands must be numbers.".to_owned();
+                    self.error_kind = Some(ErrorKind::Type);
                     return Err(Status::RuntimeError);
                 }
                 self.push(Value::from(a $op b));
             }
         }
 
-        if self.funcs.is_empty() {
-            functions::init(self);
-        }
+        // operands are coerced as `Value::compare()` describes
+        macro_rules! comparison_op {
+            ($op:tt) => {
+                let b = self.pop();
+                let a = self.pop();
 
-        Compiler::new(&mut *self, source).compile()?;
+                match Value::compare(&a, &b, self) {
+                    Ok(ordering) => {
+                        let is_true = ordering.is_some_and(|ordering| ordering $op Ordering::Equal);
+                        self.push(Value::boolean(is_true));
+                    }
+                    Err(err_msg) => {
+                        self.error = err_msg;
+                        self.error_kind = Some(ErrorKind::Type);
+                        return Err(Status::RuntimeError);
+                    }
+                }
+            }
+        }
 
         let trace_exec = cfg!(debug_assertions) && option_env!("TRACE_EXEC").is_some();
 
@@ -181,181 +547,189 @@ impl Vm {
                     }
                     for val in &self.stack {
                         print!("[ {STYLE_YELLOW}");
-                        val.print(&self.strings.borrow());
+                        val.print(&self.strings);
                         print!("{STYLE_RESET} ]");
                     }
                     println!();
                 }
-                debug::disassemble_instruction(
-                    &self.bc,
-                    self.current_offset,
-                    &self.strings.borrow(),
-                );
+                debug::disassemble_instruction(&self.bc, self.current_offset, &self.strings);
             }
 
-            let opcode = self.read_byte();
+            self.count_instruction()?;
+            let byte = self.read_byte();
+            let Ok(opcode) = Opcode::try_from(byte) else {
+                self.error = format!("Undefined instruction: {byte}.");
+
+                return Err(Status::RuntimeError);
+            };
 
             match opcode {
-                b if b == Opcode::Constant as u8 || b == Opcode::ConstantShort as u8 => {
-                    let offset = if b == Opcode::ConstantShort as u8 {
-                        self.read_bytes() as usize
-                    } else {
-                        self.read_byte() as usize
-                    };
+                Opcode::Constant | Opcode::ConstantShort => {
+                    let offset = self.read_index(opcode.is_short());
                     let val = self.read_const(offset);
 
                     self.push(val);
                 }
 
-                b if b == Opcode::DefineGlobal as u8 || b == Opcode::DefineGlobalShort as u8 => {
-                    let offset = if b == Opcode::DefineGlobalShort as u8 {
-                        self.read_bytes() as usize
-                    } else {
-                        self.read_byte() as usize
-                    };
-                    let ident = self.read_const(offset);
-                    let string_obj: StringObj = ident.into();
-                    let hash = string_obj.hash;
+                Opcode::DefineGlobal | Opcode::DefineGlobalShort => {
+                    let slot = self.read_index(opcode.is_short());
+
+                    self.globals[slot] = Some(self.pop());
+                }
 
-                    self.globals.insert(hash, self.peek(0).clone());
+                Opcode::DeclareGlobal | Opcode::DeclareGlobalShort => {
+                    let slot = self.read_index(opcode.is_short());
+
+                    // `var` doesn't reset a variable the host defined
+                    if !self.host_globals.contains(&slot) {
+                        self.globals[slot] = Some(self.peek(0).clone());
+                    }
                     self.pop();
                 }
 
-                b if b == Opcode::GetGlobal as u8 || b == Opcode::GetGlobalShort as u8 => {
-                    let offset = if b == Opcode::GetGlobalShort as u8 {
-                        self.read_bytes() as usize
-                    } else {
-                        self.read_byte() as usize
-                    };
-                    let ident = self.read_const(offset);
-                    let string_obj: StringObj = ident.into();
-                    let name = string_obj.get(&self.strings.borrow()).to_owned();
-                    let hash = string_obj.hash;
+                Opcode::GetGlobal | Opcode::GetGlobalShort => {
+                    let slot = self.read_index(opcode.is_short());
 
-                    let Some(val) = self.globals.get(&hash) else {
-                        *self.result.borrow_mut() = format!("Undefined variable: `{name}`.");
-                        return Err(Status::RuntimeError);
+                    let Some(val) = &self.globals[slot] else {
+                        return Err(self.undefined_global(slot));
                     };
 
                     self.push(val.clone());
                 }
 
-                b if b == Opcode::SetGlobal as u8 || b == Opcode::SetGlobalShort as u8 => {
-                    let offset = if b == Opcode::SetGlobalShort as u8 {
-                        self.read_bytes() as usize
-                    } else {
-                        self.read_byte() as usize
-                    };
+                Opcode::SetGlobal | Opcode::SetGlobalShort => {
+                    let slot = self.read_index(opcode.is_short());
+                    if self.globals[slot].is_none() {
+                        return Err(self.undefined_global(slot));
+                    }
+                    self.globals[slot] = Some(self.peek(0).clone());
+                }
+
+                Opcode::GetAttr | Opcode::GetAttrShort => {
+                    let offset = self.read_index(opcode.is_short());
                     let ident = self.read_const(offset);
                     let string_obj: StringObj = ident.into();
-                    let name = string_obj.get(&self.strings.borrow()).to_owned();
-                    let hash = string_obj.hash;
-                    if !self.globals.contains_key(&hash) {
-                        *self.result.borrow_mut() = format!("Undefined variable: `{name}`.");
+                    let symbol = string_obj.symbol;
 
+                    let Some(val) = self.attrs.get(&symbol) else {
+                        self.error =
+                            format!("Undefined attribute: `{}`.", string_obj.get(&self.strings));
+                        self.error_kind = Some(ErrorKind::UndefinedAttribute);
                         return Err(Status::RuntimeError);
-                    }
-                    self.globals.insert(hash, self.peek(0).clone());
+                    };
+
+                    self.push(val.clone());
                 }
 
-                b if b == Opcode::NativeFn as u8 || b == Opcode::NativeFnShort as u8 => {
-                    let offset = if b == Opcode::NativeFnShort as u8 {
-                        self.read_bytes() as usize
-                    } else {
-                        self.read_byte() as usize
-                    };
+                Opcode::NativeFn | Opcode::NativeFnShort => {
+                    let offset = self.read_index(opcode.is_short());
                     let ident = self.read_const(offset);
                     let string_obj: StringObj = ident.into();
-                    let hash = string_obj.hash;
+                    let symbol = string_obj.symbol;
 
-                    let Some(val) = self.funcs.get(&hash) else {
-                        *self.result.borrow_mut() = format!(
-                            "Undefined function: `{}`.",
-                            string_obj.get(&self.strings.borrow())
-                        );
+                    let Some(val) = self.funcs.get(&symbol) else {
+                        self.error =
+                            format!("Undefined function: `{}`.", string_obj.get(&self.strings));
+                        self.error_kind = Some(ErrorKind::UndefinedFunction);
                         return Err(Status::RuntimeError);
                     };
 
                     self.push(val.clone());
                 }
 
-                b if b == Opcode::Call as u8 => {
+                Opcode::Call => {
                     let arg_count = self.read_byte();
                     let callee = self.peek(arg_count as usize);
+                    let arg_start = self.stack.len() - arg_count as usize;
+                    let started = self.observer.is_some().then(Instant::now);
 
-                    if callee.is_nfn() {
+                    let (symbol, result) = if callee.is_nfn() {
                         let obj: NativeFnObj = (*callee).clone().into();
                         let nfn = obj.nfn;
-                        let arg_start = self.stack.len() - arg_count as usize;
-                        let result = nfn(arg_start, arg_count, self);
 
-                        // only returned `Value` should remain
-                        for _ in 0..=arg_count {
-                            self.pop();
-                        }
+                        (obj.symbol, nfn(arg_start, arg_count, self))
+                    } else if callee.is_hfn() {
+                        let obj: HostFnObj = (*callee).clone().into();
 
-                        match result {
-                            Ok(val) => self.push(val),
-                            Err(val) => {
-                                self.write(&val);
-                                return Err(Status::RuntimeError);
-                            }
-                        }
+                        (obj.symbol, host::call(&obj, arg_start, arg_count, self))
                     } else {
-                        *self.result.borrow_mut() = "Callee is not callable.".to_owned();
+                        self.error = "Callee is not callable.".to_owned();
 
                         return Err(Status::RuntimeError);
+                    };
+
+                    if let Some(started) = started {
+                        let args = &self.stack[arg_start..];
+                        self.observe_call(symbol, args, &result, started.elapsed());
+                    }
+
+                    // only returned `Value` should remain
+                    for _ in 0..=arg_count {
+                        self.pop();
+                    }
+
+                    match result {
+                        Ok(val) => self.push(val),
+                        Err(val) => {
+                            self.error_function = self.strings.get(symbol).map(str::to_owned);
+                            if let Some(status) = self.usage.halt.take() {
+                                return Err(status);
+                            }
+                            self.write(&val, out)?;
+                            return Err(Status::RuntimeError);
+                        }
                     }
                 }
 
                 //
-                b if b == Opcode::GetLocal as u8 => {
+                Opcode::GetLocal => {
                     let offset = self.read_byte() as usize;
 
                     self.push(self.stack[offset].clone());
                 }
 
-                b if b == Opcode::SetLocal as u8 => {
+                Opcode::SetLocal => {
                     let offset = self.read_byte() as usize;
 
                     self.stack[offset] = self.peek(0).clone();
                 }
 
                 //
-                b if b == Opcode::Jump as u8 => {
+                Opcode::Jump => {
                     let offset = self.read_bytes() as usize;
 
                     self.current_offset += offset;
                 }
 
-                b if b == Opcode::JumpIfFalse as u8 => {
+                Opcode::JumpIfFalse => {
                     let offset = self.read_bytes() as usize;
                     if !self.peek(0).is_truthy(self) {
                         self.current_offset += offset;
                     }
                 }
 
-                b if b == Opcode::Loop as u8 => {
+                Opcode::Loop => {
                     let offset = self.read_bytes() as usize;
 
                     self.current_offset -= offset;
                 }
 
-                b if b == Opcode::Pass as u8 => {
+                Opcode::Pass => {
                     let end: f64 = (*self).pop().into();
                     let start: f64 = (*self).pop().into();
 
-                    *self.result.borrow_mut() += &source[start as usize..end as usize];
+                    self.emit(&source[start as usize..end as usize], out)?;
                 }
 
                 //
-                b if b == Opcode::Add as u8 => {
+                Opcode::Add => {
                     binary_op!(+);
                 }
 
-                b if b == Opcode::Negate as u8 => {
+                Opcode::Negate => {
                     if !self.peek(0).is_num() {
-                        *self.result.borrow_mut() = "Operand must be a number.".to_owned();
+                        self.error = "Operand must be a number.".to_owned();
+                        self.error_kind = Some(ErrorKind::Type);
 
                         return Err(Status::RuntimeError);
                     }
@@ -364,80 +738,74 @@ impl Vm {
                     self.push(Value::num(-num));
                 }
 
-                b if b == Opcode::Null as u8 => {
+                Opcode::Null => {
                     self.push(Value::null());
                 }
 
-                b if b == Opcode::LineFeed as u8 => {
-                    *self.result.borrow_mut() += "\n";
+                Opcode::LineFeed => {
+                    self.emit("\n", out)?;
                 }
 
                 //
-                b if b == Opcode::True as u8 => {
+                Opcode::True => {
                     self.push(Value::boolean(true));
                 }
 
-                b if b == Opcode::False as u8 => {
+                Opcode::False => {
                     self.push(Value::boolean(false));
                 }
 
-                b if b == Opcode::Not as u8 => {
+                Opcode::Not => {
                     let val = self.pop();
                     self.push(Value::boolean(!val.is_truthy(self)));
                 }
 
                 //
-                b if b == Opcode::Equal as u8 => {
+                Opcode::Equal => {
                     let b = self.pop();
                     let a = self.pop();
-                    let result = Value::are_vals_eq(&a, &b, &self.strings.borrow());
+                    let result = Value::are_vals_eq(&a, &b, self);
 
                     self.push(Value::boolean(result));
                 }
 
-                b if b == Opcode::NotEqual as u8 => {
+                Opcode::NotEqual => {
                     let b = self.pop();
                     let a = self.pop();
-                    let result = !Value::are_vals_eq(&a, &b, &self.strings.borrow());
+                    let result = !Value::are_vals_eq(&a, &b, self);
 
                     self.push(Value::boolean(result));
                 }
 
-                b if b == Opcode::Less as u8 => {
-                    binary_op!(<);
+                Opcode::Less => {
+                    comparison_op!(<);
                 }
 
-                b if b == Opcode::LessEqual as u8 => {
-                    binary_op!(<=);
+                Opcode::LessEqual => {
+                    comparison_op!(<=);
                 }
 
-                b if b == Opcode::Greater as u8 => {
-                    binary_op!(>);
+                Opcode::Greater => {
+                    comparison_op!(>);
                 }
 
-                b if b == Opcode::GreaterEqual as u8 => {
-                    binary_op!(>=);
+                Opcode::GreaterEqual => {
+                    comparison_op!(>=);
                 }
 
                 //
-                b if b == Opcode::Pop as u8 => {
+                Opcode::Pop => {
                     self.pop();
                 }
 
-                b if b == Opcode::Write as u8 => {
+                Opcode::Write => {
                     let val = self.pop();
-                    self.write(&val);
+                    self.write(&val, out)?;
                 }
 
-                b if b == Opcode::Return as u8 => {
+                Opcode::Return => {
                     return Ok(());
                 }
-
-                _ => {
-                    *self.result.borrow_mut() = format!("Undefined instruction: {opcode}.");
-
-                    return Err(Status::RuntimeError);
-                }
             }
         }
     }
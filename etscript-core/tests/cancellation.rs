@@ -0,0 +1,88 @@
+// A `CancelToken` stops a render running on another thread, keeping whatever it output.
+
+mod common;
+
+use etscript_core::{CancelToken, Context, Engine, ErrorKind, HostValue, Limits};
+
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// would run for minutes if it weren't cancelled
+const LONG_LOOP: &str = "%%[ var @i for @i = 1 to 1000000000 do ]%%.%%=Started()=%%%%[ next @i ]%%";
+
+// `Started()` tells the test, through `sender`, that the loop is running
+fn engine(sender: mpsc::Sender<()>) -> Engine {
+    let sender = Mutex::new(Some(sender));
+    let mut engine = common::engine(false);
+    engine
+        .register_fn("Started", 0..=0, move |_| {
+            if let Some(sender) = sender.lock().unwrap().take() {
+                sender.send(()).unwrap();
+            }
+            Ok(HostValue::Null)
+        })
+        .unwrap();
+    // fails the test instead of hanging it if cancelling doesn't work
+    engine.set_limits(Limits {
+        wall_time: Some(Duration::from_secs(30)),
+        ..Limits::default()
+    });
+    engine
+}
+
+//
+
+#[test]
+fn cancel_from_another_thread() {
+    let (sender, receiver) = mpsc::channel();
+    let template = engine(sender).compile(LONG_LOOP).unwrap();
+    let token = CancelToken::new();
+    let context = Context::new().cancel_token(token.clone());
+
+    let err = thread::scope(|scope| {
+        let render = scope.spawn(|| template.render_with(&context));
+
+        receiver.recv().unwrap();
+        token.cancel();
+        render.join().unwrap()
+    })
+    .expect_err("The render should have been cancelled.");
+
+    assert_eq!(err.kind(), ErrorKind::Cancelled, "{err}");
+
+    let output = err.partial_output().unwrap();
+    let progress = err.progress().unwrap();
+    assert!(!output.is_empty() && output.chars().all(|c| c == '.'));
+    assert_eq!(progress.output_bytes, output.len() as u64);
+    assert!(progress.instructions > 0);
+}
+
+#[test]
+fn cancel_before_render() {
+    let (sender, _receiver) = mpsc::channel();
+    let token = CancelToken::new();
+    token.cancel();
+
+    let err = engine(sender)
+        .render_with(LONG_LOOP, &Context::new().cancel_token(token))
+        .expect_err("The render should have been cancelled.");
+
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    assert_eq!(err.partial_output(), Some(""));
+    assert_eq!(err.progress().unwrap().instructions, 0);
+}
+
+#[test]
+fn token_only_cancels_its_own_renders() {
+    let (sender, _receiver) = mpsc::channel();
+    let token = CancelToken::new();
+    token.cancel();
+    let context = Context::new().cancel_token(CancelToken::new());
+
+    assert!(token.is_cancelled());
+    assert_eq!(
+        engine(sender).render_with("done", &context).unwrap().output,
+        "done"
+    );
+}
@@ -0,0 +1,113 @@
+// Strings interned while rendering are collected once there are thousands of them. Everything
+// still reachable has to survive each collection with its value intact.
+
+mod common;
+
+use etscript_core::{Context, HostValue, Rendered};
+
+use std::collections::BTreeMap;
+
+// enough distinct strings to be collected several times
+const ITERATIONS: u32 = 20_000;
+
+fn render(source: &str) -> Rendered {
+    let row = BTreeMap::from([
+        ("tier".to_owned(), HostValue::from("Gold")),
+        ("email".to_owned(), HostValue::from("ann@example.com")),
+    ]);
+    let context = Context::new()
+        .attribute("First Name", "Ann")
+        .variable("@member", HostValue::Row(row.clone()))
+        .variable("@members", HostValue::Rowset(vec![row]))
+        .variable("@greeting", "Hello");
+
+    common::engine(false).render_with(source, &context).unwrap()
+}
+
+fn string<'a>(rendered: &'a Rendered, name: &str) -> &'a str {
+    rendered.variables[name]
+        .as_str()
+        .unwrap_or_else(|| panic!("`{name}` should have been a string."))
+}
+
+//
+
+#[test]
+fn reachable_strings_survive_collection() {
+    let source = format!(
+        "%%[
+var @i, @scratch, @before, @last, @tier, @email, @first
+set @before = Concat(@greeting, \", \", [First Name])
+for @i = 1 to {ITERATIONS} do
+  set @scratch = Concat(\"scratch \", @i)
+  set @last = Concat(@before, \" #\", @i)
+next @i
+set @tier = Field(@member, \"tier\")
+set @email = Field(Row(@members, 1), \"email\")
+set @first = [First Name]
+]%%%%=v(@before)=%%|%%=v(@last)=%%|%%=v(@tier)=%%|%%=v(@email)=%%|%%=v(@first)=%%"
+    );
+    let rendered = render(&source);
+
+    assert_eq!(
+        rendered.output,
+        format!("Hello, Ann|Hello, Ann #{ITERATIONS}|Gold|ann@example.com|Ann")
+    );
+    assert_eq!(string(&rendered, "@before"), "Hello, Ann");
+    assert_eq!(
+        string(&rendered, "@last"),
+        format!("Hello, Ann #{ITERATIONS}")
+    );
+    assert_eq!(
+        string(&rendered, "@scratch"),
+        format!("scratch {ITERATIONS}")
+    );
+    assert_eq!(string(&rendered, "@tier"), "Gold");
+    assert_eq!(string(&rendered, "@email"), "ann@example.com");
+    assert_eq!(string(&rendered, "@first"), "Ann");
+}
+
+#[test]
+fn host_variables_survive_collection() {
+    let source = format!(
+        "%%[
+var @i, @scratch
+for @i = 1 to {ITERATIONS} do
+  set @scratch = Concat(@greeting, @i)
+next @i
+]%%"
+    );
+    let rendered = render(&source);
+
+    assert_eq!(string(&rendered, "@greeting"), "Hello");
+
+    let HostValue::Row(member) = &rendered.variables["@member"] else {
+        panic!("`@member` should have been a row.");
+    };
+    assert_eq!(member["tier"].as_str(), Some("Gold"));
+    assert_eq!(member["email"].as_str(), Some("ann@example.com"));
+
+    let HostValue::Rowset(members) = &rendered.variables["@members"] else {
+        panic!("`@members` should have been a rowset.");
+    };
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0]["email"].as_str(), Some("ann@example.com"));
+}
+
+// the strings in a row are only reachable through the row while the loop runs
+#[test]
+fn strings_of_rows_survive_collection() {
+    let source = format!(
+        "%%[
+var @i, @tier, @row
+set @row = Row(@members, 1)
+for @i = 1 to {ITERATIONS} do
+  set @tier = Concat(Field(@row, \"tier\"), @i)
+next @i
+]%%%%=v(Field(@row, \"email\"))=%%"
+    );
+    let rendered = render(&source);
+
+    assert_eq!(rendered.output, "ann@example.com");
+    assert_eq!(string(&rendered, "@tier"), format!("Gold{ITERATIONS}"));
+}
@@ -0,0 +1,22 @@
+// Fixtures shared by the integration tests. Each test file includes this with `mod common;` and
+// uses only some of it.
+#![allow(dead_code)]
+
+use etscript_core::{Engine, ErrorKind};
+
+// The engine that every test file starts from. It's given no database, since one is only
+// opened once a data extension function is called; tests that call one set their own, e.g.,
+// `Database::in_memory()`, so that nothing is written to `./etscript.db`.
+pub fn engine(is_strict: bool) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_strict(is_strict);
+    engine
+}
+
+// the output of rendering `source`, or the kind of error that stopped it
+pub fn render(engine: &Engine, source: &str) -> Result<String, ErrorKind> {
+    engine
+        .render(source)
+        .map(|rendered| rendered.output)
+        .map_err(|err| err.kind())
+}
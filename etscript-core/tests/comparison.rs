@@ -0,0 +1,336 @@
+// One test per row of the coercion table in the README's "Comparisons" section.
+
+mod common;
+
+use etscript_core::{Clock, Context, Engine, ErrorKind, HostValue};
+
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+
+// `Now()` returns 2024-01-01T00:00:00Z
+const NOW: u64 = 1_704_067_200;
+
+fn engine(is_strict: bool) -> Engine {
+    let mut engine = common::engine(is_strict);
+    engine.set_clock(Clock::Fixed(UNIX_EPOCH + Duration::from_secs(NOW)));
+    engine
+}
+
+// `@null` is null, `@nan` is NaN, `@row` is a row, and `@rowset` is a rowset
+fn context() -> Context {
+    let row = BTreeMap::from([("id".to_owned(), HostValue::from(1.0))]);
+
+    Context::new()
+        .variable("@nan", HostValue::from(f64::NAN))
+        .variable("@row", HostValue::Row(row.clone()))
+        .variable("@rowset", HostValue::Rowset(vec![row]))
+}
+
+fn eval_with(engine: &Engine, expr: &str) -> Result<bool, ErrorKind> {
+    let source = format!("%%[ var @null, @result set @result = {expr} ]%%");
+    let rendered = engine
+        .render_with(&source, &context())
+        .map_err(|err| err.kind())?;
+
+    Ok(rendered.variables["@result"]
+        .as_bool()
+        .unwrap_or_else(|| panic!("`{expr}` should have been a boolean.")))
+}
+
+fn assert_cells(cells: &[(&str, bool)]) {
+    let engine = engine(false);
+
+    for (expr, expected) in cells {
+        assert_eq!(eval_with(&engine, expr), Ok(*expected), "`{expr}`");
+    }
+}
+
+fn assert_errors(exprs: &[&str]) {
+    let engine = engine(false);
+
+    for expr in exprs {
+        assert_eq!(eval_with(&engine, expr), Err(ErrorKind::Type), "`{expr}`");
+    }
+}
+
+//
+
+#[test]
+fn null_and_null() {
+    assert_cells(&[
+        ("@null == @null", true),
+        ("@null != @null", false),
+        ("@null < @null", false),
+        ("@null <= @null", true),
+        ("@null > @null", false),
+        ("@null >= @null", true),
+    ]);
+}
+
+#[test]
+fn null_and_anything_else() {
+    assert_cells(&[
+        ("@null == 0", false),
+        ("@null == \"\"", false),
+        ("@null == false", false),
+        ("@null != Now()", true),
+        ("@null < 0", true),
+        ("@null < -1", true),
+        ("@null < \"\"", true),
+        ("@null < false", true),
+        ("@null < Now()", true),
+        ("0 > @null", true),
+        ("\"a\" >= @null", true),
+        ("Now() <= @null", false),
+    ]);
+}
+
+#[test]
+fn date_and_date() {
+    assert_cells(&[
+        ("Now() == Now()", true),
+        ("Now() != Now()", false),
+        (
+            "DateParse(\"2024-01-01\") == DateParse(\"2024-01-01\")",
+            true,
+        ),
+        (
+            "DateParse(\"2023-06-15\") == DateParse(\"2024-01-01\")",
+            false,
+        ),
+        (
+            "DateParse(\"2023-06-15\") < DateParse(\"2024-01-01\")",
+            true,
+        ),
+        (
+            "DateParse(\"2023-06-15\") <= DateParse(\"2023-06-15\")",
+            true,
+        ),
+        (
+            "DateParse(\"2023-06-15\") > DateParse(\"2024-01-01\")",
+            false,
+        ),
+        (
+            "DateParse(\"2024-01-01\") >= DateParse(\"2023-06-15\")",
+            true,
+        ),
+    ]);
+}
+
+#[test]
+fn date_and_string() {
+    assert_cells(&[
+        ("DateParse(\"2024-01-01\") == \"2024-01-01\"", true),
+        ("\"2024-01-01\" == DateParse(\"2024-01-01\")", true),
+        ("DateParse(\"2024-01-01\") != \"2023-06-15\"", true),
+        ("DateParse(\"2024-01-01\") == \"not a date\"", false),
+        ("Now() > \"2000-01-01\"", true),
+        ("Now() < \"2999-12-31\"", true),
+        ("\"2000-01-01\" <= Now()", true),
+        ("\"2999-12-31\" >= Now()", true),
+    ]);
+    assert_errors(&["Now() < \"not a date\"", "\"\" >= Now()"]);
+}
+
+#[test]
+fn date_and_number_or_boolean() {
+    assert_cells(&[
+        ("Now() == 0", false),
+        ("2024 == Now()", false),
+        ("Now() == true", false),
+        ("false == Now()", false),
+        ("Now() != false", true),
+    ]);
+    assert_errors(&["Now() > 0", "0 <= Now()", "Now() < true", "false >= Now()"]);
+}
+
+#[test]
+fn boolean_and_boolean() {
+    assert_cells(&[
+        ("true == true", true),
+        ("true != false", true),
+        ("false < true", true),
+        ("true < false", false),
+        ("true <= true", true),
+        ("true > false", true),
+        ("false >= true", false),
+    ]);
+}
+
+#[test]
+fn boolean_and_number() {
+    assert_cells(&[
+        ("true == 1", true),
+        ("1 == true", true),
+        ("true == 2", false),
+        ("false == 0", true),
+        ("false == 2", true),
+        ("0 < true", true),
+        ("1 > false", true),
+        ("2 > false", false),
+        ("true >= 1", true),
+        ("false <= -5", true),
+    ]);
+}
+
+#[test]
+fn boolean_and_string() {
+    assert_cells(&[
+        ("true == \"true\"", true),
+        ("true == \"YES\"", true),
+        ("\"y\" == true", true),
+        ("true == \"1\"", true),
+        ("true == \"no\"", false),
+        ("false == \"no\"", true),
+        ("false == \"anything\"", true),
+        ("\"f\" < true", true),
+        ("\"t\" > false", true),
+        ("\"T\" >= true", true),
+        ("\"banana\" <= false", true),
+    ]);
+}
+
+#[test]
+fn number_and_number() {
+    assert_cells(&[
+        ("1 == 1", true),
+        ("1 != 1.5", true),
+        ("-1 < 0", true),
+        ("2.5 <= 2.5", true),
+        ("10 > 9", true),
+        ("9 >= 10", false),
+    ]);
+}
+
+#[test]
+fn number_and_numeric_string() {
+    assert_cells(&[
+        ("\"5\" == 5", true),
+        ("5 == \"5.0\"", true),
+        ("\"5\" != 6", true),
+        ("\"5\" > 3", true),
+        ("3 < \"5\"", true),
+        ("\"10\" > 9", true),
+        ("\"-2.5\" <= -2.5", true),
+        ("100 >= \"99.9\"", true),
+    ]);
+}
+
+#[test]
+fn number_and_any_other_string() {
+    assert_cells(&[
+        ("\"abc\" == 5", false),
+        ("5 != \"five\"", true),
+        ("0 == \"\"", false),
+        // only plain decimals are numeric strings
+        ("\"05\" == 5", false),
+        ("5 == \"+5\"", false),
+        ("\"1e3\" == 1000", false),
+        ("\".5\" == 0.5", false),
+        ("5 == \"5.\"", false),
+    ]);
+    assert_errors(&[
+        "\"abc\" > 3",
+        "3 < \"abc\"",
+        "\"\" <= 0",
+        "0 >= \"5 apples\"",
+        "\"inf\" > 3",
+        "\"-05\" < 0",
+    ]);
+}
+
+#[test]
+fn numeric_string_and_numeric_string() {
+    assert_cells(&[
+        ("\"5\" == \"5\"", true),
+        ("\"5\" == \"5.0\"", true),
+        ("\"-0\" == \"0\"", true),
+        ("\"5\" != \"6\"", true),
+        ("\"10\" > \"9\"", true),
+        ("\"9\" < \"10\"", true),
+        ("\"5\" <= \"5.0\"", true),
+        ("\"5.0\" >= \"5\"", true),
+    ]);
+}
+
+// equality agrees with ordering: neither `<` nor `>` means `==`
+#[test]
+fn numeric_strings_that_are_equal_as_numbers() {
+    assert_cells(&[
+        ("\"1.0\" == \"1\"", true),
+        ("\"1.0\" != \"1\"", false),
+        ("\"1.0\" < \"1\"", false),
+        ("\"1.0\" > \"1\"", false),
+        ("\"1.0\" <= \"1\"", true),
+        ("\"1.0\" >= \"1\"", true),
+    ]);
+}
+
+#[test]
+fn nan_and_number_or_numeric_string() {
+    assert_cells(&[
+        ("@nan == @nan", false),
+        ("@nan != @nan", true),
+        ("@nan == 1", false),
+        ("\"1\" == @nan", false),
+        ("@nan < @nan", false),
+        ("@nan >= @nan", false),
+        ("@nan < 1", false),
+        ("@nan <= 1", false),
+        ("@nan > 1", false),
+        ("@nan >= 1", false),
+        ("1 < @nan", false),
+        ("\"1\" > @nan", false),
+        ("@nan <= \"1\"", false),
+    ]);
+}
+
+#[test]
+fn string_and_string() {
+    assert_cells(&[
+        ("\"Gold\" == \"GOLD\"", true),
+        ("\"Gold\" != \"gold\"", false),
+        ("\"Gold\" == \"Silver\"", false),
+        ("\"apple\" < \"Banana\"", true),
+        ("\"Banana\" > \"apple\"", true),
+        ("\"abc\" <= \"ABC\"", true),
+        ("\"abc\" >= \"ABD\"", false),
+        ("\"10 apples\" < \"9 apples\"", true),
+        ("\"5\" == \"05\"", false),
+        ("\"1e3\" == \"1000\"", false),
+        ("\"inf\" > \"1000\"", true),
+        ("\"\" < \"a\"", true),
+    ]);
+}
+
+#[test]
+fn string_and_string_when_strict() {
+    let engine = engine(true);
+
+    for (expr, expected) in [
+        ("\"Gold\" == \"GOLD\"", false),
+        ("\"Gold\" == \"Gold\"", true),
+        ("\"Banana\" < \"apple\"", true),
+        ("\"abc\" <= \"ABC\"", false),
+    ] {
+        assert_eq!(eval_with(&engine, expr), Ok(expected), "`{expr}`");
+    }
+}
+
+// an error stops the render before it can be compared, so only rows and rowsets are covered
+#[test]
+fn row_or_rowset_and_anything() {
+    assert_cells(&[
+        ("@row == @row", false),
+        ("@row != 1", true),
+        ("@rowset == @null", false),
+        ("\"a\" == @rowset", false),
+    ]);
+    assert_errors(&[
+        "@row < @row",
+        "@row > 1",
+        "@rowset <= @null",
+        "\"a\" >= @rowset",
+        "true < @row",
+    ]);
+}
@@ -0,0 +1,68 @@
+// The database is opened, and bootstrapped, only once a data extension function is called, and
+// a read-only one can't be written to.
+
+use etscript_core::{Database, Engine, ErrorKind};
+
+use std::{env, fs, path::PathBuf, process};
+
+// a path of its own for each test, so that tests running at the same time don't share a file
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("etscript-{}-{name}.db", process::id()))
+}
+
+fn engine(path: &PathBuf) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_database(Database::file(path));
+    engine
+}
+
+//
+
+#[test]
+fn render_without_data_extension_calls_touches_no_file() {
+    let path = temp_path("untouched");
+    let engine = engine(&path);
+    let template = engine.compile("Hello %%=Add(1, 2)=%%").unwrap();
+
+    assert_eq!(template.render().unwrap().output, "Hello 3");
+    assert!(!path.exists());
+}
+
+#[test]
+fn first_data_extension_call_creates_the_file() {
+    let path = temp_path("created");
+    let engine = engine(&path);
+    let template = engine
+        .compile("%%=DataExtensionRowCount(\"_test_table\")=%%")
+        .unwrap();
+
+    assert!(!path.exists());
+    assert_eq!(template.render().unwrap().output, "0");
+    assert!(path.exists());
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn writes_to_a_read_only_database_fail() {
+    let mut engine = Engine::new();
+    engine.set_database(Database::in_memory().unwrap().read_only(true));
+
+    for source in [
+        "%%[ InsertDe(\"_test_table\", \"email\", \"ann@example.com\") ]%%",
+        "%%[ UpsertDe(\"_test_table\", 1, \"email\", \"ann@example.com\", \"locale\", \"en-us\") ]%%",
+        "%%[ UpdateDe(\"_test_table\", 1, \"email\", \"ann@example.com\", \"locale\", \"en-us\") ]%%",
+        "%%[ DeleteDe(\"_test_table\", \"email\", \"ann@example.com\") ]%%",
+    ] {
+        let err = engine.render(source).expect_err(source);
+        assert_eq!(err.kind(), ErrorKind::Database, "{source}: {err}");
+    }
+    // reads still work
+    assert_eq!(
+        engine
+            .render("%%=DataExtensionRowCount(\"_test_table\")=%%")
+            .unwrap()
+            .output,
+        "0"
+    );
+}
@@ -0,0 +1,58 @@
+// With a fixed clock and a seed, `Now()`, `Random()`, and `Guid()` give the same output on every
+// render, e.g., for snapshot tests.
+
+mod common;
+
+use etscript_core::{Clock, Engine};
+
+use std::time::{Duration, UNIX_EPOCH};
+
+// 2024-01-01T00:00:00Z
+const NOW: u64 = 1_704_067_200;
+
+const SOURCE: &str = "%%=Now()=%% %%=Random(1, 1000000)=%% %%=Random(1, 1000000)=%% %%=Guid()=%%";
+
+fn engine(seed: Option<u64>) -> Engine {
+    let mut engine = common::engine(false);
+    engine.set_clock(Clock::Fixed(UNIX_EPOCH + Duration::from_secs(NOW)));
+    engine.set_seed(seed);
+    engine
+}
+
+//
+
+#[test]
+fn renders_of_a_template_match() {
+    let template = engine(Some(42)).compile(SOURCE).unwrap();
+    let first = template.render().unwrap().output;
+
+    assert_eq!(template.render().unwrap().output, first);
+}
+
+#[test]
+fn engines_with_the_same_settings_match() {
+    let first = engine(Some(42)).render(SOURCE).unwrap().output;
+
+    assert_eq!(engine(Some(42)).render(SOURCE).unwrap().output, first);
+}
+
+#[test]
+fn seed_decides_the_random_values() {
+    let first = engine(Some(42)).render(SOURCE).unwrap().output;
+    let other = engine(Some(43)).render(SOURCE).unwrap().output;
+
+    assert_ne!(other, first);
+    // the time doesn't depend on the seed
+    let time = |output: &str| output.split(' ').next().unwrap().to_owned();
+    assert_eq!(time(&other), time(&first));
+}
+
+#[test]
+fn unseeded_renders_differ() {
+    let template = engine(None).compile(SOURCE).unwrap();
+
+    assert_ne!(
+        template.render().unwrap().output,
+        template.render().unwrap().output
+    );
+}
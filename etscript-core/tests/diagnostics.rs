@@ -0,0 +1,161 @@
+// The compiler reports every error in the source, each with its position and span, and the
+// warnings of source that compiles. `Diagnostic::render` shows each with its line.
+
+mod common;
+
+use common::engine;
+use etscript_core::{Database, Diagnostic, ErrorKind, Severity, Status};
+
+// the severity, line, column, spanned text, and message of each diagnostic
+fn summarize<'a>(
+    diagnostics: &'a [Diagnostic],
+    source: &'a str,
+) -> Vec<(Severity, u32, u32, Option<&'a str>, &'a str)> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic.severity,
+                diagnostic.line,
+                diagnostic.column,
+                diagnostic.span.clone().map(|span| &source[span]),
+                diagnostic.message.as_str(),
+            )
+        })
+        .collect()
+}
+
+//
+
+#[test]
+fn every_compile_error_is_reported() {
+    let source = "%%[ set @a = ]%%
+hello
+%%[ set @b = (1 ]%%
+%%[ set @c = 1 + ]%%
+%%=Foo(=%%";
+    let err = engine(false).compile(source).err().unwrap();
+
+    assert_eq!(err.status(), Status::CompileError);
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    assert_eq!((err.line(), err.column()), (1, 14));
+    assert_eq!(
+        summarize(err.diagnostics(), source),
+        [
+            (Severity::Error, 1, 14, Some("]%%"), "Expected expression."),
+            (
+                Severity::Error,
+                3,
+                17,
+                Some("]%%"),
+                "Expected `)` after expression."
+            ),
+            (
+                Severity::Error,
+                4,
+                16,
+                Some("+"),
+                "Unexpected character: `+`."
+            ),
+            (Severity::Error, 5, 8, Some("=%%"), "Expected expression."),
+        ]
+    );
+}
+
+#[test]
+fn error_at_end_of_source_has_empty_span() {
+    let source = "%%[ set @a = 1";
+    let err = engine(false).compile(source).err().unwrap();
+    let diagnostic = &err.diagnostics()[0];
+
+    assert_eq!(diagnostic.span, Some(source.len()..source.len()));
+    assert_eq!((diagnostic.line, diagnostic.column), (1, 15));
+}
+
+#[test]
+fn variable_spelled_two_ways_is_a_warning() {
+    let source = "%%[ set @Name = \"Ann\" ]%%\n%%=v(@name)=%%";
+    let template = engine(false).compile(source).unwrap();
+
+    assert_eq!(
+        summarize(template.warnings(), source),
+        [(
+            Severity::Warning,
+            2,
+            6,
+            Some("@name"),
+            "`@name` is the same variable as `@Name`, since variable names ignore case."
+        )]
+    );
+
+    let rendered = template.render().unwrap();
+    assert_eq!(rendered.output, "\nAnn");
+    assert_eq!(rendered.warnings, template.warnings());
+}
+
+#[test]
+fn strict_engines_dont_warn_about_spelling() {
+    let source = "%%[ set @Name = \"Ann\" ]%%%%=v(@Name)=%%";
+    let template = engine(true).compile(source).unwrap();
+
+    assert!(template.warnings().is_empty());
+}
+
+#[test]
+fn runtime_error_has_a_position_but_no_span() {
+    let source = "%%[ set @a = 1 ]%%\n  %%=v(@zz)=%%";
+    let err = engine(false).render(source).unwrap_err();
+
+    assert_eq!(err.status(), Status::RuntimeError);
+    assert_eq!(
+        summarize(err.diagnostics(), source),
+        [(Severity::Error, 2, 8, None, "Undefined variable: `@zz`.")]
+    );
+}
+
+#[test]
+fn render_underlines_the_span() {
+    let source = "%%[ set @a = 1 ]%%\n%%[ set @b = (1 ]%%";
+    let err = engine(false).compile(source).err().unwrap();
+
+    assert_eq!(
+        err.diagnostics()[0].render(source, false),
+        "error: Expected `)` after expression.
+ --> line 2, column 17
+  |
+2 | %%[ set @b = (1 ]%%
+  |                 ^^^"
+    );
+    assert!(err.diagnostics()[0].render(source, true).contains('\x1b'));
+}
+
+#[test]
+fn render_without_a_column_has_no_carets() {
+    let diagnostic = Diagnostic {
+        severity: Severity::Warning,
+        line: 1,
+        column: 0,
+        span: None,
+        message: "Careful.".to_owned(),
+    };
+
+    assert_eq!(
+        diagnostic.render("\tsome source", false),
+        "warning: Careful.\n --> line 1\n  |\n1 | \tsome source"
+    );
+}
+
+// a function's error reads like any other runtime error, without an `Error: ` prefix
+#[test]
+fn function_error_message_is_bare() {
+    let mut engine = engine(false);
+    engine.set_database(Database::in_memory().unwrap());
+    let err = engine
+        .render("%%=Lookup(\"NoSuchTable\", \"a\", \"b\", \"c\")=%%")
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Database);
+    assert_eq!(err.function(), Some("lookup"));
+    assert_eq!(err.message(), "No such table: nosuchtable");
+    assert_eq!(err.diagnostics()[0].message, err.message());
+}
@@ -0,0 +1,251 @@
+// The C ABI, called from Rust the way a C host would call it: values and strings cross in both
+// directions, `user_data` reaches every callback, and each result is freed by its own function.
+
+use etscript_core::ffi::{
+    etscript_engine_free, etscript_engine_new, etscript_engine_register_fn, etscript_engine_render,
+    etscript_engine_set_attribute, etscript_engine_set_database_in_memory,
+    etscript_engine_set_variable, etscript_free_render_result, free_result, interpret, EtEngine,
+    EtValue, EtValueKind,
+};
+use etscript_core::{ErrorKind, Status};
+
+use std::{
+    ffi::{c_char, c_void, CStr, CString},
+    ptr, slice,
+};
+
+// what `Greet()` is given through `user_data`
+struct Greeter {
+    greeting: CString,
+    error: CString,
+    calls: usize,
+}
+
+// `Greet(name)` returns the greeting and the name, `Greet()` fails, and any other argument
+// returns a value of an unknown kind
+unsafe extern "C" fn greet(
+    args: *const EtValue,
+    arg_count: usize,
+    out: *mut EtValue,
+    user_data: *mut c_void,
+) -> bool {
+    let greeter = &mut *(user_data as *mut Greeter);
+    let args = slice::from_raw_parts(args, arg_count);
+    greeter.calls += 1;
+
+    match args.first() {
+        None => {
+            (*out).string = greeter.error.as_ptr();
+            false
+        }
+        Some(arg) if arg.kind == EtValueKind::String as u32 => {
+            let name = CStr::from_ptr(arg.string).to_str().unwrap();
+            greeter.greeting = CString::new(format!("Hello, {name}")).unwrap();
+            (*out).kind = EtValueKind::String as u32;
+            (*out).string = greeter.greeting.as_ptr();
+            true
+        }
+        Some(_) => {
+            (*out).kind = 99;
+            true
+        }
+    }
+}
+
+fn c(string: &str) -> CString {
+    CString::new(string).unwrap()
+}
+
+unsafe fn engine(greeter: &mut Greeter) -> *mut EtEngine {
+    let engine = etscript_engine_new();
+    assert!(etscript_engine_set_database_in_memory(engine));
+    assert!(etscript_engine_register_fn(
+        engine,
+        c("Greet").as_ptr(),
+        0,
+        1,
+        greet,
+        greeter as *mut Greeter as *mut c_void,
+    ));
+    engine
+}
+
+fn greeter() -> Greeter {
+    Greeter {
+        greeting: CString::default(),
+        error: c("No one to greet."),
+        calls: 0,
+    }
+}
+
+// a render's status, error kind, output, and error message, which is `None` if it's null
+unsafe fn render(engine: *const EtEngine, source: &str) -> (Status, i32, String, Option<String>) {
+    let result = etscript_engine_render(engine, c(source).as_ptr());
+    let string = |ptr: *mut c_char| {
+        (!ptr.is_null()).then(|| CStr::from_ptr(ptr).to_str().unwrap().to_owned())
+    };
+    let rendered = (
+        (*result).status,
+        (*result).error_kind,
+        string((*result).output).unwrap(),
+        string((*result).error),
+    );
+
+    etscript_free_render_result(result);
+    rendered
+}
+
+//
+
+#[test]
+fn callback_gets_arguments_and_user_data() {
+    let mut greeter = greeter();
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        assert!(etscript_engine_set_attribute(
+            engine,
+            c("First Name").as_ptr(),
+            c("Ann").as_ptr()
+        ));
+
+        let rendered = render(engine, "%%=Greet([First Name])=%%!");
+        assert_eq!(rendered, (Status::Ok, 0, "Hello, Ann!".to_owned(), None));
+        etscript_engine_free(engine);
+    }
+    // the engine is gone, and the host still owns what it passed as `user_data`
+    assert_eq!(greeter.calls, 1);
+    assert_eq!(greeter.greeting.to_str(), Ok("Hello, Ann"));
+}
+
+#[test]
+fn callback_failure_is_a_host_error() {
+    let mut greeter = greeter();
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        let (status, error_kind, output, error) = render(engine, "before %%=Greet()=%%");
+
+        assert_eq!(status, Status::RuntimeError);
+        assert_eq!(error_kind, ErrorKind::Host as i32);
+        assert_eq!(output, "before ");
+        assert_eq!(error.as_deref(), Some("No one to greet."));
+        etscript_engine_free(engine);
+    }
+}
+
+#[test]
+fn callback_arity_is_checked() {
+    let mut greeter = greeter();
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        let (status, error_kind, ..) = render(engine, "%%=Greet(\"Ann\", \"Bob\")=%%");
+
+        assert_eq!(status, Status::RuntimeError);
+        assert_eq!(error_kind, ErrorKind::Arity as i32);
+        etscript_engine_free(engine);
+    }
+    assert_eq!(greeter.calls, 0);
+}
+
+#[test]
+fn unknown_value_kinds_are_rejected() {
+    let mut greeter = greeter();
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        let (_, error_kind, _, error) = render(engine, "%%=Greet(1)=%%");
+        assert_eq!(error_kind, ErrorKind::Host as i32);
+        assert_eq!(
+            error.as_deref(),
+            Some("Callback returned a value of unknown kind: 99.")
+        );
+
+        let value = EtValue {
+            kind: 4,
+            number: 0.0,
+            boolean: false,
+            string: ptr::null(),
+        };
+        assert!(!etscript_engine_set_variable(
+            engine,
+            c("@a").as_ptr(),
+            &value
+        ));
+        etscript_engine_free(engine);
+    }
+}
+
+#[test]
+fn variables_cross_as_values() {
+    let mut greeter = greeter();
+    let name = c("Bob");
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        let value = EtValue {
+            kind: EtValueKind::String as u32,
+            number: 0.0,
+            boolean: false,
+            string: name.as_ptr(),
+        };
+        assert!(etscript_engine_set_variable(
+            engine,
+            c("@name").as_ptr(),
+            &value
+        ));
+
+        let (_, _, output, _) = render(engine, "%%=Greet(@name)=%%");
+        assert_eq!(output, "Hello, Bob");
+        etscript_engine_free(engine);
+    }
+}
+
+#[test]
+fn interpret_result_owns_its_value() {
+    unsafe {
+        let result = interpret(c("%%=Add(1, 2)=%%").as_ptr());
+        assert_eq!((*result).status, Status::Ok as i32);
+        assert_eq!(CStr::from_ptr((*result).value).to_str(), Ok("3"));
+        free_result(result);
+
+        // runtime errors keep the prefix that `interpret` has always given them
+        let result = interpret(c("%%=Row(\"\", 1)=%%").as_ptr());
+        assert_eq!((*result).status, Status::RuntimeError as i32);
+        assert!(CStr::from_ptr((*result).value)
+            .to_str()
+            .unwrap()
+            .starts_with("Error: "));
+        free_result(result);
+
+        free_result(ptr::null_mut());
+    }
+}
+
+#[test]
+fn register_fn_refuses_what_it_cant_register() {
+    let mut greeter = greeter();
+    let user_data = &mut greeter as *mut Greeter as *mut c_void;
+
+    unsafe {
+        let engine = engine(&mut greeter);
+        assert!(!etscript_engine_register_fn(
+            engine,
+            c("Lookup").as_ptr(),
+            0,
+            1,
+            greet,
+            user_data
+        ));
+        assert!(!etscript_engine_register_fn(
+            engine,
+            c("Greet").as_ptr(),
+            2,
+            1,
+            greet,
+            user_data
+        ));
+        etscript_engine_free(engine);
+    }
+}
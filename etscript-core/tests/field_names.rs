@@ -0,0 +1,84 @@
+// Field names from a data extension ignore case like AMPscript's, unless the engine is strict,
+// in which case a field has to be spelled as its column is.
+
+mod common;
+
+use common::render;
+use etscript_core::{Database, Engine, ErrorKind};
+
+fn engine(is_strict: bool) -> Engine {
+    let database = Database::in_memory().unwrap().bootstrap(
+        "CREATE TABLE Subscribers (EmailAddress TEXT, FirstName TEXT); \
+        INSERT INTO Subscribers VALUES ('ann@example.com', 'Ann');",
+    );
+    let mut engine = common::engine(is_strict);
+    engine.set_database(database);
+    engine
+}
+
+fn lookup(column: &str) -> String {
+    format!("%%=Lookup(\"Subscribers\", \"{column}\", \"emailaddress\", \"ann@example.com\")=%%")
+}
+
+fn field_of_lookup_rows(column: &str) -> String {
+    format!(
+        "%%[ var @rows set @rows = LookupRows(\"Subscribers\", \"EMAILADDRESS\", \"ann@example.com\") ]%%\
+        %%=Field(Row(@rows, 1), \"{column}\")=%%"
+    )
+}
+
+//
+
+#[test]
+fn lookup_ignores_case() {
+    let engine = engine(false);
+
+    for column in ["FirstName", "firstname", "FIRSTNAME"] {
+        assert_eq!(render(&engine, &lookup(column)).as_deref(), Ok("Ann"));
+    }
+}
+
+#[test]
+fn field_of_lookup_rows_ignores_case() {
+    let engine = engine(false);
+
+    for column in ["FirstName", "firstname", "FIRSTNAME"] {
+        assert_eq!(
+            render(&engine, &field_of_lookup_rows(column)).as_deref(),
+            Ok("Ann")
+        );
+    }
+}
+
+#[test]
+fn strict_lookup_matches_case() {
+    let engine = engine(true);
+
+    assert_eq!(render(&engine, &lookup("FirstName")).as_deref(), Ok("Ann"));
+    assert_eq!(
+        render(&engine, &lookup("firstname")),
+        Err(ErrorKind::Database)
+    );
+}
+
+#[test]
+fn strict_field_of_lookup_rows_matches_case() {
+    let engine = engine(true);
+
+    assert_eq!(
+        render(&engine, &field_of_lookup_rows("FirstName")).as_deref(),
+        Ok("Ann")
+    );
+    assert!(render(&engine, &field_of_lookup_rows("firstname")).is_err());
+}
+
+// no row means no value, however the column is spelled
+#[test]
+fn lookup_without_a_match_is_empty() {
+    for is_strict in [false, true] {
+        let source =
+            "%%=Lookup(\"Subscribers\", \"FirstName\", \"EmailAddress\", \"bob@example.com\")=%%";
+
+        assert_eq!(render(&engine(is_strict), source).as_deref(), Ok(""));
+    }
+}
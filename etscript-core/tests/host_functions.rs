@@ -0,0 +1,135 @@
+// Functions registered by the host are called like built-ins: their arguments and return values
+// convert to and from `HostValue`, their arity is checked, and their errors stop the render.
+
+mod common;
+
+use common::render;
+use etscript_core::{Context, Engine, ErrorKind, HostValue};
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+fn engine() -> Engine {
+    let mut engine = common::engine(false);
+    engine
+        .register_fn("Echo", 1..=1, |args| Ok(args[0].clone()))
+        .unwrap();
+    engine
+}
+
+//
+
+#[test]
+fn values_convert_both_ways() {
+    let row = BTreeMap::from([
+        ("id".to_owned(), HostValue::from(1.0)),
+        ("name".to_owned(), HostValue::from("Ann")),
+    ]);
+    let context = Context::new()
+        .variable("@row", HostValue::Row(row.clone()))
+        .variable("@rowset", HostValue::Rowset(vec![row.clone()]));
+    let source = "%%[ var @null
+        set @number = Echo(1.5) set @boolean = Echo(true) set @string = Echo(\"hi\")
+        set @nothing = Echo(@null) set @row_copy = Echo(@row) set @rowset_copy = Echo(@rowset)
+    ]%%%%=Field(Echo(@row), \"name\")=%%";
+    let rendered = engine().render_with(source, &context).unwrap();
+    let variables = &rendered.variables;
+
+    assert_eq!(rendered.output, "Ann");
+    assert_eq!(variables["@number"], HostValue::Number(1.5));
+    assert_eq!(variables["@boolean"], HostValue::Boolean(true));
+    assert_eq!(variables["@string"], HostValue::from("hi"));
+    assert_eq!(variables["@nothing"], HostValue::Null);
+    assert_eq!(variables["@row_copy"], HostValue::Row(row.clone()));
+    assert_eq!(variables["@rowset_copy"], HostValue::Rowset(vec![row]));
+}
+
+#[test]
+fn arity_is_checked_before_the_call() {
+    let mut engine = engine();
+    engine
+        .register_fn("AtLeastTwo", 2.., |args| Ok((args.len() as f64).into()))
+        .unwrap();
+    engine
+        .register_fn("AtMostOne", ..2, |args| Ok((args.len() as f64).into()))
+        .unwrap();
+
+    assert_eq!(render(&engine, "%%=Echo()=%%"), Err(ErrorKind::Arity));
+    assert_eq!(render(&engine, "%%=Echo(1, 2)=%%"), Err(ErrorKind::Arity));
+    assert_eq!(
+        render(&engine, "%%=AtLeastTwo(1)=%%"),
+        Err(ErrorKind::Arity)
+    );
+    assert_eq!(
+        render(&engine, "%%=AtLeastTwo(1, 2, 3, 4)=%%").as_deref(),
+        Ok("4")
+    );
+    assert_eq!(render(&engine, "%%=AtMostOne()=%%").as_deref(), Ok("0"));
+    assert_eq!(
+        render(&engine, "%%=AtMostOne(1, 2)=%%"),
+        Err(ErrorKind::Arity)
+    );
+}
+
+#[test]
+fn error_stops_the_render() {
+    let mut engine = engine();
+    engine
+        .register_fn("Fail", 0..=0, |_| Err("Out of coupons.".to_owned()))
+        .unwrap();
+    let err = engine.render("before %%=Fail()=%% after").unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::Host);
+    assert_eq!(err.message(), "Out of coupons.");
+    assert_eq!(err.function(), Some("fail"));
+    assert_eq!(err.partial_output(), Some("before "));
+}
+
+#[test]
+fn arity_that_allows_nothing_is_rejected() {
+    let mut engine = engine();
+
+    let err = engine
+        .register_fn("Never", (Bound::Included(2), Bound::Included(1)), |_| {
+            Ok(HostValue::Null)
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Input);
+    assert!(engine
+        .register_fn("Never", 1..1, |_| Ok(HostValue::Null))
+        .is_err());
+    assert_eq!(
+        render(&engine, "%%=Never()=%%"),
+        Err(ErrorKind::UndefinedFunction)
+    );
+}
+
+#[test]
+fn builtin_names_are_rejected_until_unregistered() {
+    let mut engine = engine();
+
+    for name in ["Lookup", "LOOKUP"] {
+        let err = engine
+            .register_fn(name, 0..=0, |_| Ok("mocked".into()))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Input);
+    }
+
+    assert!(engine.unregister_fn("lookup"));
+    engine
+        .register_fn("Lookup", 0..=0, |_| Ok("mocked".into()))
+        .unwrap();
+    assert_eq!(render(&engine, "%%=Lookup()=%%").as_deref(), Ok("mocked"));
+}
+
+#[test]
+fn registering_again_replaces_the_function() {
+    let mut engine = engine();
+    engine
+        .register_fn("echo", 1..=1, |args| {
+            Ok(format!("{}!", args[0].as_str().unwrap_or_default()).into())
+        })
+        .unwrap();
+
+    assert_eq!(render(&engine, "%%=Echo(\"hi\")=%%").as_deref(), Ok("hi!"));
+}
@@ -0,0 +1,137 @@
+// A limit stops the render with what was output before it, and `Progress` counts only the work
+// that was allowed to happen.
+
+mod common;
+
+use etscript_core::{Database, Engine, ErrorKind, EtError, Limits};
+
+use std::time::Duration;
+
+const ROW_COUNT: &str = "%%=DataExtensionRowCount(\"_test_table\")=%%";
+
+fn engine(limits: Limits) -> Engine {
+    let mut engine = common::engine(false);
+    // for `ROW_COUNT`
+    engine.set_database(Database::in_memory().unwrap());
+    engine.set_limits(limits);
+    engine
+}
+
+fn render_err(engine: &Engine, source: &str) -> EtError {
+    let err = engine
+        .render(source)
+        .expect_err("The render should have exceeded a limit.");
+
+    assert_eq!(err.kind(), ErrorKind::LimitExceeded, "{err}");
+    err
+}
+
+//
+
+#[test]
+fn instruction_limit() {
+    let engine = engine(Limits {
+        instructions: Some(1_000),
+        ..Limits::default()
+    });
+    let err = render_err(
+        &engine,
+        "before %%[ var @i for @i = 1 to 1000000 do ]%%.%%[ next @i ]%%",
+    );
+
+    assert!(err.partial_output().unwrap().starts_with("before ."));
+    assert_eq!(err.progress().unwrap().instructions, 1_001);
+}
+
+#[test]
+fn output_limit_keeps_what_fit() {
+    let engine = engine(Limits {
+        output_bytes: Some(10),
+        ..Limits::default()
+    });
+    let err = render_err(&engine, "12345%%=v(\"67890abc\")=%%more");
+
+    assert_eq!(err.partial_output(), Some("12345"));
+    // the rejected chunk isn't counted
+    assert_eq!(err.progress().unwrap().output_bytes, 5);
+}
+
+#[test]
+fn output_limit_allows_exactly_the_limit() {
+    let engine = engine(Limits {
+        output_bytes: Some(10),
+        ..Limits::default()
+    });
+
+    assert_eq!(
+        engine.render("12345%%=v(\"67890\")=%%").unwrap().output,
+        "1234567890"
+    );
+}
+
+#[test]
+fn data_extension_call_limit() {
+    let engine = engine(Limits {
+        data_extension_calls: Some(1),
+        ..Limits::default()
+    });
+    let err = render_err(&engine, &format!("{ROW_COUNT} {ROW_COUNT}"));
+
+    assert_eq!(err.partial_output(), Some("0 "));
+    // the refused call was never made
+    assert_eq!(err.progress().unwrap().data_extension_calls, 1);
+}
+
+#[test]
+fn data_extension_call_limit_of_zero() {
+    let engine = engine(Limits {
+        data_extension_calls: Some(0),
+        ..Limits::default()
+    });
+    let err = render_err(&engine, &format!("first {ROW_COUNT}"));
+
+    assert_eq!(err.partial_output(), Some("first "));
+    assert_eq!(err.progress().unwrap().data_extension_calls, 0);
+}
+
+#[test]
+fn wall_time_limit() {
+    let engine = engine(Limits {
+        wall_time: Some(Duration::from_millis(10)),
+        ..Limits::default()
+    });
+    let err = render_err(
+        &engine,
+        "%%[ var @i for @i = 1 to 1000000000 do set @a = @i next @i ]%%",
+    );
+
+    assert!(err.progress().unwrap().instructions > 0);
+}
+
+// each render gets the full limit
+#[test]
+fn limits_apply_to_each_render() {
+    let engine = engine(Limits {
+        instructions: Some(1_000),
+        output_bytes: Some(5),
+        ..Limits::default()
+    });
+    let template = engine.compile("%%=v(\"12345\")=%%").unwrap();
+
+    for _ in 0..3 {
+        assert_eq!(template.render().unwrap().output, "12345");
+    }
+}
+
+#[test]
+fn partial_output_can_be_discarded() {
+    let mut engine = engine(Limits {
+        output_bytes: Some(3),
+        ..Limits::default()
+    });
+    engine.set_partial_output(false);
+    let err = render_err(&engine, "abc%%=v(\"def\")=%%");
+
+    assert_eq!(err.partial_output(), None);
+    assert_eq!(err.progress().unwrap().output_bytes, 3);
+}
@@ -0,0 +1,137 @@
+// An `Observer` hears about each function call, SQL statement, and chunk of output, in order.
+
+mod common;
+
+use etscript_core::{Call, Context, Database, Engine, HostValue, Observer};
+
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct Recorder {
+    events: Mutex<Vec<String>>,
+}
+
+impl Observer for Recorder {
+    fn on_call(&self, call: &Call) {
+        let args = call
+            .args()
+            .iter()
+            .map(|arg| format!("{arg:?}"))
+            .collect::<Vec<_>>();
+        let event = format!(
+            "call {}({}) -> {:?}",
+            call.name,
+            args.join(", "),
+            call.result()
+        );
+
+        self.events.lock().unwrap().push(event);
+    }
+
+    fn on_query(&self, sql: &str) {
+        self.events.lock().unwrap().push(format!("query {sql}"));
+    }
+
+    fn on_output(&self, chunk: &str) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("output {chunk:?}"));
+    }
+}
+
+// the events recorded while rendering `source`
+fn events(engine: &Engine, source: &str) -> Vec<String> {
+    let recorder = Arc::new(Recorder::default());
+    let context = Context::new().observer(recorder.clone());
+    let _ = engine.render_with(source, &context);
+
+    let events = recorder.events.lock().unwrap();
+    events.clone()
+}
+
+fn engine() -> Engine {
+    let mut engine = common::engine(false);
+    engine.set_database(Database::in_memory().unwrap());
+    engine
+        .register_fn("Tier", 1..=1, |args| match args[0].as_number() {
+            Some(points) if points >= 1000.0 => Ok("Gold".into()),
+            Some(_) => Ok("Silver".into()),
+            None => Err("Points must be a number.".to_owned()),
+        })
+        .unwrap();
+    engine
+}
+
+//
+
+#[test]
+fn calls_and_output_in_order() {
+    assert_eq!(
+        events(&engine(), "Tier: %%=Tier(Add(999, 1))=%%."),
+        [
+            "output \"Tier: \"",
+            "call add(Number(999.0), Number(1.0)) -> Ok(Number(1000.0))",
+            "call tier(Number(1000.0)) -> Ok(String(\"Gold\"))",
+            "output \"Gold\"",
+            "output \".\"",
+        ]
+    );
+}
+
+#[test]
+fn failed_call_has_the_error_message() {
+    assert_eq!(
+        events(&engine(), "%%=Tier(\"lots\")=%%"),
+        ["call tier(String(\"lots\")) -> Err(\"Points must be a number.\")"]
+    );
+}
+
+#[test]
+fn rows_are_passed_as_host_values() {
+    let events = events(
+        &engine(),
+        "%%[ set @row = Row(BuildRowsetFromString(\"a\", \"|\"), 1) ]%%%%=Field(@row, 1)=%%",
+    );
+
+    assert!(events[2].starts_with("call field(Row({"), "{events:?}");
+}
+
+#[test]
+fn queries_precede_their_calls() {
+    let events = events(&engine(), "%%=DataExtensionRowCount(\"_test_table\")=%%");
+    let [.., query, call, output] = events.as_slice() else {
+        panic!("{events:?}");
+    };
+
+    assert!(query.starts_with("query SELECT"), "{query}");
+    assert_eq!(
+        call,
+        "call dataextensionrowcount(String(\"_test_table\")) -> Ok(Number(0.0))"
+    );
+    assert_eq!(output, "output \"0\"");
+}
+
+// an observer converts only the arguments it looks at
+#[test]
+fn call_args_can_be_read_one_at_a_time() {
+    struct FirstArg(Mutex<Vec<(usize, Option<HostValue>)>>);
+
+    impl Observer for FirstArg {
+        fn on_call(&self, call: &Call) {
+            let mut seen = self.0.lock().unwrap();
+            seen.push((call.arg_count(), call.arg(0)));
+        }
+    }
+
+    let observer = Arc::new(FirstArg(Mutex::default()));
+    let context = Context::new().observer(observer.clone());
+    engine()
+        .render_with("%%=Concat(\"a\", \"b\", \"c\")=%%%%=Guid()=%%", &context)
+        .unwrap();
+
+    assert_eq!(
+        *observer.0.lock().unwrap(),
+        [(3, Some(HostValue::from("a"))), (0, None)]
+    );
+}
@@ -0,0 +1,135 @@
+// Variables live in slots that the compiler assigns, which every block of a template shares. A
+// render's `variables` are what the slots held when it finished.
+
+mod common;
+
+use common::engine;
+use etscript_core::{Context, ErrorKind, HostValue};
+
+fn render(source: &str) -> Result<String, ErrorKind> {
+    common::render(&engine(false), source)
+}
+
+//
+
+#[test]
+fn reading_before_setting_is_an_error() {
+    let err = engine(false)
+        .render("before %%=v(@a)=%%%%[ set @a = 1 ]%%")
+        .unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::UndefinedVariable);
+    assert_eq!(err.message(), "Undefined variable: `@a`.");
+    assert_eq!(err.partial_output(), Some("before "));
+    // a `set` that doesn't run doesn't define the variable
+    assert_eq!(
+        render("%%[ if 1 == 2 then set @a = 1 endif ]%%%%=v(@a)=%%"),
+        Err(ErrorKind::UndefinedVariable)
+    );
+}
+
+#[test]
+fn declared_variable_is_empty() {
+    assert_eq!(render("[%%[ var @a ]%%%%=v(@a)=%%]").as_deref(), Ok("[]"));
+    assert_eq!(
+        render("%%[ var @a, @b set @b = Empty(@a) ]%%%%=v(@b)=%%").as_deref(),
+        Ok("true")
+    );
+}
+
+#[test]
+fn declaring_again_empties_the_variable() {
+    let rendered = engine(false)
+        .render("%%[ set @a = 1 var @a ]%%[%%=v(@a)=%%]")
+        .unwrap();
+
+    assert_eq!(rendered.output, "[]");
+    assert_eq!(rendered.variables["@a"], HostValue::Null);
+}
+
+#[test]
+fn blocks_share_variables() {
+    let source = "%%[ var @greeting set @greeting = \"Hi\" ]%%\
+        %%=v(@greeting)=%%, %%[ set @name = \"Ann\" ]%%%%=v(@name)=%%\
+        %%[ set @greeting = Concat(@greeting, \" \", @name) ]%%! %%=v(@greeting)=%%";
+
+    assert_eq!(render(source).as_deref(), Ok("Hi, Ann! Hi Ann"));
+}
+
+#[test]
+fn names_ignore_case() {
+    assert_eq!(
+        render("%%[ set @Total = 2 set @TOTAL = Add(@total, 1) ]%%%%=v(@toTal)=%%").as_deref(),
+        Ok("3")
+    );
+}
+
+#[test]
+fn rendered_variables_are_the_final_values() {
+    let source = "%%[ var @unset, @Segment set @Segment = \"gold\" set @count = 0
+        for @i = 1 to 3 do set @count = Add(@count, @i) next @i
+        set @row = Row(BuildRowsetFromString(\"a|b\", \"|\"), 2) ]%%";
+    let rendered = engine(false).render(source).unwrap();
+    let names = rendered
+        .variables
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    assert_eq!(names, ["@count", "@i", "@row", "@segment", "@unset"]);
+    assert_eq!(rendered.variables["@unset"], HostValue::Null);
+    assert_eq!(rendered.variables["@segment"], HostValue::from("gold"));
+    assert_eq!(rendered.variables["@count"], HostValue::from(6.0));
+    assert!(matches!(rendered.variables["@row"], HostValue::Row(_)));
+}
+
+#[test]
+fn strict_variables_keep_their_case() {
+    let rendered = engine(true)
+        .render("%%[ set @Segment = \"gold\" ]%%")
+        .unwrap();
+
+    assert_eq!(rendered.variables["@Segment"], HostValue::from("gold"));
+    assert_eq!(
+        engine(true)
+            .render("%%[ set @Segment = \"gold\" ]%%%%=v(@segment)=%%")
+            .unwrap_err()
+            .kind(),
+        ErrorKind::UndefinedVariable
+    );
+}
+
+#[test]
+fn host_variables_survive_var_but_not_set() {
+    let context = Context::new()
+        .variable("@tier", HostValue::from("gold"))
+        .variable("@unused", HostValue::from(1.0));
+    let engine = engine(false);
+
+    let rendered = engine
+        .render_with("%%[ var @tier ]%%%%=v(@tier)=%%", &context)
+        .unwrap();
+    assert_eq!(rendered.output, "gold");
+    // a variable the source never mentions is still exported
+    assert_eq!(rendered.variables["@unused"], HostValue::from(1.0));
+
+    let rendered = engine
+        .render_with("%%[ set @tier = \"silver\" ]%%%%=v(@tier)=%%", &context)
+        .unwrap();
+    assert_eq!(rendered.output, "silver");
+    assert_eq!(rendered.variables["@tier"], HostValue::from("silver"));
+}
+
+// a render starts with only the host's variables, whatever an earlier one set
+#[test]
+fn renders_dont_share_variables() {
+    let template = engine(false)
+        .compile("%%[ if Empty(@seen) then set @seen = 1 else set @seen = Add(@seen, 1) endif ]%%")
+        .unwrap();
+    let context = Context::new().variable("@seen", HostValue::Null);
+
+    for _ in 0..2 {
+        let rendered = template.render_with(&context).unwrap();
+        assert_eq!(rendered.variables["@seen"], HostValue::from(1.0));
+    }
+}
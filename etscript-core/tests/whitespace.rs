@@ -0,0 +1,116 @@
+// Whitespace around blocks: `Whitespace::Preserve` and `Whitespace::SwallowNewline`, and the
+// `%%[-` and `-]%%` trim markers, which work the same in either mode.
+
+mod common;
+
+use etscript_core::{Engine, Whitespace};
+
+fn engine(whitespace: Whitespace) -> Engine {
+    let mut engine = common::engine(false);
+    engine.set_whitespace(whitespace);
+    engine
+}
+
+fn assert_renders(whitespace: Whitespace, cases: &[(&str, &str)]) {
+    let engine = engine(whitespace);
+
+    for (source, expected) in cases {
+        let output = engine.render(source).unwrap().output;
+        assert_eq!(output, *expected, "{whitespace:?}: {source:?}");
+    }
+}
+
+fn assert_renders_either_way(cases: &[(&str, &str)]) {
+    assert_renders(Whitespace::Preserve, cases);
+    assert_renders(Whitespace::SwallowNewline, cases);
+}
+
+//
+
+#[test]
+fn preserve() {
+    assert_renders(
+        Whitespace::Preserve,
+        &[
+            ("%%[ set @a = 1 ]%%\nHello", "\nHello"),
+            ("%%[ set @a = 1 ]%%\r\nHello", "\r\nHello"),
+            ("Hi \n%%[ set @a = 1 ]%%\n\nHello", "Hi \n\n\nHello"),
+        ],
+    );
+}
+
+#[test]
+fn swallow_newline() {
+    assert_renders(
+        Whitespace::SwallowNewline,
+        &[
+            ("%%[ set @a = 1 ]%%\nHello", "Hello"),
+            ("%%[ set @a = 1 ]%%\r\nHello", "Hello"),
+            // only the one newline directly after the block
+            ("%%[ set @a = 1 ]%%\n\nHello", "\nHello"),
+            ("%%[ set @a = 1 ]%% \nHello", " \nHello"),
+            ("Hi \n%%[ set @a = 1 ]%%\nHello", "Hi \nHello"),
+            // inline expressions aren't blocks
+            ("%%=v(1)=%%\nHello", "1\nHello"),
+            (
+                "<script runat=\"server\" language=\"ampscript\">set @a = 1</script>\nHello",
+                "Hello",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn trim_after() {
+    assert_renders_either_way(&[
+        ("%%[ set @a = 1 -]%%\n \n\tHello", "Hello"),
+        ("%%[ set @a = 1 -]%%Hello", "Hello"),
+        ("%%[ set @a = 1 -]%%\n", ""),
+    ]);
+}
+
+#[test]
+fn trim_before() {
+    assert_renders(
+        Whitespace::Preserve,
+        &[
+            ("Hi \n\t %%[- set @a = 1 ]%%\nHello", "Hi\nHello"),
+            ("Hi%%[- set @a = 1 ]%%", "Hi"),
+        ],
+    );
+    assert_renders(
+        Whitespace::SwallowNewline,
+        &[("Hi \n\t %%[- set @a = 1 ]%%\nHello", "HiHello")],
+    );
+}
+
+#[test]
+fn trim_both_sides() {
+    assert_renders_either_way(&[
+        ("a \n %%[- set @a = 1 -]%% \n b", "ab"),
+        (
+            "<p>\n  %%[- set @greeting = \"Hi\" -]%%\n  %%=v(@greeting)=%%\n</p>",
+            "<p>Hi\n</p>",
+        ),
+    ]);
+}
+
+#[test]
+fn trim_inside_control_flow() {
+    let source = "%%[ var @i for @i = 1 to 3 do -]%%\n  %%=v(@i)=%%\n%%[- next @i ]%%";
+
+    assert_renders(Whitespace::Preserve, &[(source, "123")]);
+    assert_renders(Whitespace::SwallowNewline, &[(source, "123")]);
+}
+
+// the lines of later errors still count the trimmed newlines
+#[test]
+fn trimmed_newlines_still_count() {
+    for whitespace in [Whitespace::Preserve, Whitespace::SwallowNewline] {
+        let err = engine(whitespace)
+            .render("%%[ set @a = 1 -]%%\n\n%%[- set @b = 2 ]%%\n%%=v(@zz)=%%")
+            .unwrap_err();
+
+        assert_eq!(err.line(), 4, "{whitespace:?}");
+    }
+}